@@ -0,0 +1,64 @@
+// Times SpatialHash against checking every pair of rects (the collision
+// tests check that they agree). Run with `cargo bench --bench broadphase`.
+use engine2d::{
+    collision::{self, SpatialHash},
+    objects::Rect,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::time::{Duration, Instant};
+
+const WORLD: f32 = 4096.0;
+const RUNS: u32 = 10;
+
+// Small-ish rects scattered over a big world, like bullets and enemies
+fn scatter(count: usize, rng: &mut StdRng) -> Vec<Rect> {
+    (0..count)
+        .map(|_| {
+            let w = rng.gen_range(4.0..32.0);
+            let h = rng.gen_range(4.0..32.0);
+            Rect::new(
+                rng.gen_range(0.0..WORLD - w),
+                rng.gen_range(0.0..WORLD - h),
+                w,
+                h,
+            )
+        })
+        .collect()
+}
+
+// Average time per run, and how many pairs the last one found
+fn time(mut f: impl FnMut(&mut Vec<(usize, usize)>)) -> (Duration, usize) {
+    let mut pairs = vec![];
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        pairs.clear();
+        f(&mut pairs);
+    }
+    (start.elapsed() / RUNS, pairs.len())
+}
+
+fn main() {
+    let mut rng = StdRng::seed_from_u64(2021);
+    let mut hash = SpatialHash::new(32.0);
+    println!(
+        "{:>6} {:>8} {:>14} {:>14} {:>8}",
+        "rects", "pairs", "brute force", "spatial hash", "speedup"
+    );
+    for &count in [250, 1000, 4000, 16000].iter() {
+        let rects = scatter(count, &mut rng);
+        let (brute, _) = time(|pairs| collision::brute_force_pairs(&rects, pairs));
+        // rebuilding is part of the cost, since it happens every frame
+        let (hashed, found) = time(|pairs| {
+            hash.rebuild(&rects);
+            hash.pairs(pairs);
+        });
+        println!(
+            "{:>6} {:>8} {:>14?} {:>14?} {:>7.1}x",
+            count,
+            found,
+            brute,
+            hashed,
+            brute.as_secs_f64() / hashed.as_secs_f64()
+        );
+    }
+}
@@ -1,46 +1,370 @@
 use crate::objects::Rect;
 use std::rc::Rc;
 
-#[derive(PartialEq)]
+// What happens once the last frame is done
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Playback {
+    // Stop on the last frame
+    Once,
+    Loop,
+    // Last frame to first, then stop on the first
+    Reverse,
+    // Forward, then backward, then forward... without showing the end
+    // frames twice in a row
+    PingPong,
+    // Loop this many times, then stop on the last frame
+    Times(u32),
+    // Play once, keep showing the last frame for `hold` seconds, then
+    // switch to the Animation's default clip
+    Return { hold: f32 },
+}
+
+#[derive(Clone, PartialEq)]
 pub struct AnimationData {
-    pub frames: Vec<(Rect, usize)>, // position of frame and how many frames it'll take
-    pub looping: bool,
+    pub frames: Vec<(Rect, f32)>, // position of frame and how many seconds it stays up
+    pub playback: Playback,
+    // Reported by Animation whenever playback reaches the frame at the index
+    pub events: Vec<(usize, String)>,
+}
+
+impl AnimationData {
+    pub fn new(frames: Vec<(Rect, f32)>, playback: Playback) -> Self {
+        Self {
+            frames,
+            playback,
+            events: vec![],
+        }
+    }
+
+    // How long one play through all the frames takes, in seconds
+    pub fn duration(&self) -> f32 {
+        self.frames.iter().map(|(_, time)| time).sum()
+    }
+
+    fn first_frame(&self) -> usize {
+        match self.playback {
+            Playback::Reverse => self.frames.len() - 1,
+            _ => 0,
+        }
+    }
 }
 
 pub struct Animation {
-    current_frame: (usize, usize),
+    frame: usize,
+    // seconds spent on the current frame so far
+    elapsed: f32,
+    // passes finished so far, for Playback::Times
+    passes: u32,
+    // going toward the first frame, for Playback::PingPong
+    backward: bool,
+    // the first frame's events haven't been reported yet
+    started: bool,
+    finished: bool,
+    just_finished: bool,
+    events: Vec<String>,
+    speed: f32,
+    paused: bool,
     data: Rc<AnimationData>,
+    // what Playback::Return goes back to
+    default: Rc<AnimationData>,
 }
 
 impl Animation {
+    // `data` also becomes the default clip
     pub fn new(data: &Rc<AnimationData>) -> Self {
         Self {
-            current_frame: (0, 0),
+            frame: data.first_frame(),
+            elapsed: 0.0,
+            passes: 0,
+            backward: false,
+            started: false,
+            finished: false,
+            just_finished: false,
+            events: vec![],
+            speed: 1.0,
+            paused: false,
             data: Rc::clone(data),
+            default: Rc::clone(data),
         }
     }
 
+    // Starts over from the first frame. Speed and pausing carry over.
     pub fn set_animation(&mut self, data: &Rc<AnimationData>, force: bool) {
         if force || *data != self.data {
-            self.current_frame = (0, 0);
+            self.frame = data.first_frame();
+            self.elapsed = 0.0;
+            self.passes = 0;
+            self.backward = false;
+            self.started = false;
+            self.finished = false;
             self.data = Rc::clone(data);
         }
     }
 
-    pub fn animate(&mut self) {
-        let (frame_idx, frame_timer) = &mut self.current_frame;
-        *frame_timer += 1;
-        if *frame_timer == self.data.frames[*frame_idx].1 {
-            *frame_timer = 0;
-            if self.data.looping {
-                *frame_idx = (*frame_idx + 1) % self.data.frames.len();
-            } else {
-                *frame_idx = (*frame_idx + 1).min(self.data.frames.len() - 1);
+    pub fn set_default(&mut self, data: &Rc<AnimationData>) {
+        self.default = Rc::clone(data);
+    }
+
+    // Moves the animation `dt` seconds along. Time left over after a frame
+    // ends goes toward the next one, so a big `dt` can skip frames (their
+    // events still get reported).
+    pub fn animate(&mut self, dt: f32) {
+        self.events.clear();
+        self.just_finished = false;
+        if self.paused {
+            return;
+        }
+        if !self.started {
+            self.started = true;
+            self.enter(self.frame);
+        }
+        if self.finished || self.data.duration() <= 0.0 {
+            return;
+        }
+        self.elapsed += dt * self.speed;
+        loop {
+            let time = self.data.frames[self.frame].1;
+            if self.elapsed < time {
+                break;
+            }
+            if let Some(next) = self.next_frame() {
+                self.elapsed -= time;
+                self.frame = next;
+                self.enter(next);
+                continue;
+            }
+            // out of frames
+            match self.data.playback {
+                Playback::Return { hold } => {
+                    if self.elapsed < time + hold {
+                        break;
+                    }
+                    self.just_finished = true;
+                    let left = self.elapsed - time - hold;
+                    let default = Rc::clone(&self.default);
+                    self.set_animation(&default, true);
+                    self.started = true;
+                    self.enter(self.frame);
+                    if self.data.duration() <= 0.0 {
+                        break;
+                    }
+                    self.elapsed = left;
+                }
+                _ => {
+                    self.elapsed = time;
+                    self.finished = true;
+                    self.just_finished = true;
+                    break;
+                }
             }
         }
     }
 
+    // Where to go after the current frame; None once the clip is over
+    fn next_frame(&mut self) -> Option<usize> {
+        let (frame, last) = (self.frame, self.data.frames.len() - 1);
+        match self.data.playback {
+            Playback::Once | Playback::Return { .. } => (frame < last).then(|| frame + 1),
+            Playback::Loop => Some(if frame < last { frame + 1 } else { 0 }),
+            Playback::Reverse => frame.checked_sub(1),
+            Playback::Times(times) => {
+                if frame < last {
+                    Some(frame + 1)
+                } else {
+                    self.passes += 1;
+                    (self.passes < times).then_some(0)
+                }
+            }
+            Playback::PingPong => {
+                if last == 0 {
+                    Some(0)
+                } else {
+                    if frame == 0 || frame == last {
+                        self.backward = frame == last;
+                    }
+                    Some(if self.backward { frame - 1 } else { frame + 1 })
+                }
+            }
+        }
+    }
+
+    fn enter(&mut self, frame: usize) {
+        for (at, name) in self.data.events.iter() {
+            if *at == frame {
+                self.events.push(name.clone());
+            }
+        }
+    }
+
+    // The events of every frame reached during the last animate(), in order
+    pub fn events(&self) -> impl Iterator<Item = &str> {
+        self.events.iter().map(|name| name.as_str())
+    }
+
+    pub fn has_event(&self, name: &str) -> bool {
+        self.events().any(|event| event == name)
+    }
+
+    // 2.0 plays twice as fast, 0.5 half as fast
+    pub fn set_speed(&mut self, speed: f32) {
+        assert!(speed >= 0.0, "animation speed can't be negative");
+        self.speed = speed;
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    // How far through the clip we are, from 0.0 to 1.0. Looping clips wrap
+    // back around to 0.0 after each pass (a ping-pong pass is one way), and
+    // Times counts all of its passes.
+    pub fn progress(&self) -> f32 {
+        let total = self.data.duration();
+        if total <= 0.0 {
+            return 1.0;
+        }
+        let frames = &self.data.frames;
+        let done = if self.data.playback == Playback::Reverse || self.backward {
+            &frames[self.frame + 1..]
+        } else {
+            &frames[..self.frame]
+        };
+        let pass = done.iter().map(|(_, time)| time).sum::<f32>() + self.elapsed;
+        let progress = match self.data.playback {
+            Playback::Times(times) if times > 1 => {
+                (self.passes as f32 * total + pass) / (times as f32 * total)
+            }
+            _ => pass / total,
+        };
+        progress.min(1.0)
+    }
+
+    // Has the clip stopped for good? Looping, ping-pong and returning clips
+    // never finish.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    // Did the clip finish (or return to the default) during the last
+    // animate()?
+    pub fn just_finished(&self) -> bool {
+        self.just_finished
+    }
+
     pub fn get_current_frame(&self) -> Rect {
-        self.data.frames[self.current_frame.0].0
+        self.data.frames[self.frame].0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `count` frames a quarter second each; frame i is at x = first + i
+    fn clip(first: usize, count: usize, playback: Playback) -> Rc<AnimationData> {
+        let frames = (first..first + count)
+            .map(|i| (Rect::new(i as f32, 0.0, 1.0, 1.0), 0.25))
+            .collect();
+        Rc::new(AnimationData::new(frames, playback))
+    }
+
+    fn frame(anim: &Animation) -> usize {
+        anim.get_current_frame().x as usize
+    }
+
+    // The frame shown after each step of `dt`, starting with animate(0.0)
+    fn frames(anim: &mut Animation, dt: f32, steps: usize) -> Vec<usize> {
+        anim.animate(0.0);
+        let mut shown = vec![frame(anim)];
+        for _ in 0..steps {
+            anim.animate(dt);
+            shown.push(frame(anim));
+        }
+        shown
+    }
+
+    #[test]
+    fn times_counts_passes() {
+        let mut anim = Animation::new(&clip(0, 3, Playback::Times(2)));
+        assert_eq!(frames(&mut anim, 0.25, 5), vec![0, 1, 2, 0, 1, 2]);
+        assert!(!anim.is_finished());
+        anim.animate(0.25);
+        assert_eq!(frame(&anim), 2);
+        assert!(anim.is_finished() && anim.just_finished());
+        anim.animate(0.25);
+        assert_eq!(frame(&anim), 2);
+        assert!(anim.is_finished() && !anim.just_finished());
+    }
+
+    #[test]
+    fn ping_pong_turns_on_the_end_frames() {
+        let mut anim = Animation::new(&clip(0, 3, Playback::PingPong));
+        assert_eq!(frames(&mut anim, 0.25, 8), vec![0, 1, 2, 1, 0, 1, 2, 1, 0]);
+        assert!(!anim.is_finished() && !anim.just_finished());
+    }
+
+    #[test]
+    fn return_carries_leftover_time_into_the_default() {
+        let mut anim = Animation::new(&clip(10, 2, Playback::Loop));
+        anim.set_animation(&clip(0, 2, Playback::Return { hold: 0.5 }), false);
+        // the last frame stays up for its own time plus the hold
+        assert_eq!(frames(&mut anim, 0.25, 3), vec![0, 1, 1, 1]);
+        assert!(!anim.just_finished());
+        anim.animate(0.375);
+        assert_eq!(frame(&anim), 10);
+        assert!(anim.just_finished() && !anim.is_finished());
+        // 0.125 was already spent on frame 10
+        anim.animate(0.125);
+        assert_eq!(frame(&anim), 11);
+    }
+
+    #[test]
+    fn skipped_frames_still_report_events() {
+        let mut data = (*clip(0, 4, Playback::Once)).clone();
+        data.events = vec![
+            (0, "zero".to_string()),
+            (1, "one".to_string()),
+            (2, "two".to_string()),
+        ];
+        let mut anim = Animation::new(&Rc::new(data));
+        anim.animate(0.0);
+        assert!(anim.events().eq(["zero"].iter().copied()));
+        anim.animate(0.6);
+        assert_eq!(frame(&anim), 2);
+        assert!(anim.events().eq(["one", "two"].iter().copied()));
+        anim.animate(0.1);
+        assert_eq!(anim.events().count(), 0);
+        anim.animate(1.0);
+        assert_eq!(frame(&anim), 3);
+        assert!(anim.is_finished() && anim.just_finished());
+    }
+
+    #[test]
+    fn reverse_progress_counts_from_the_last_frame() {
+        let mut anim = Animation::new(&clip(0, 4, Playback::Reverse));
+        anim.animate(0.0);
+        assert_eq!(frame(&anim), 3);
+        assert_eq!(anim.progress(), 0.0);
+        anim.animate(0.125);
+        assert_eq!(anim.progress(), 0.125);
+        anim.animate(0.25);
+        assert_eq!(frame(&anim), 2);
+        assert_eq!(anim.progress(), 0.375);
+        anim.animate(1.0);
+        assert_eq!(frame(&anim), 0);
+        assert_eq!(anim.progress(), 1.0);
+        assert!(anim.is_finished() && anim.just_finished());
     }
 }
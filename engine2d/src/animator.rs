@@ -0,0 +1,349 @@
+use crate::animation::{Animation, AnimationData};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::rc::Rc;
+
+// Something about the animator's parameters that has to hold for a
+// transition to happen. Parameters nobody set read as false/0.0.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Condition {
+    True(String),
+    False(String),
+    Above(String, f32),
+    Below(String, f32),
+    // Set with Animator::trigger; taking the transition uses it up
+    Trigger(String),
+}
+
+pub struct AnimState {
+    pub name: String,
+    pub clip: Rc<AnimationData>,
+    pub speed: f32,
+    // Where to go once the clip finishes, if no transition went anywhere
+    pub fallback: Option<String>,
+}
+
+impl AnimState {
+    pub fn new(name: &str, clip: &Rc<AnimationData>) -> Self {
+        Self {
+            name: name.to_string(),
+            clip: Rc::clone(clip),
+            speed: 1.0,
+            fallback: None,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct AnimTransition {
+    // None goes from any state. It only goes from `to` itself (restarting
+    // it) if it has a Trigger, so a condition that keeps holding doesn't
+    // restart the clip every update.
+    pub from: Option<String>,
+    pub to: String,
+    // All of them have to hold
+    pub conditions: Vec<Condition>,
+    // How far along (0.0 to 1.0, see Animation::progress) the current clip
+    // has to be before this can happen
+    pub exit_time: Option<f32>,
+}
+
+impl AnimTransition {
+    pub fn new(from: &str, to: &str, conditions: Vec<Condition>) -> Self {
+        Self {
+            from: Some(from.to_string()),
+            to: to.to_string(),
+            conditions,
+            exit_time: None,
+        }
+    }
+
+    pub fn from_any(to: &str, conditions: Vec<Condition>) -> Self {
+        Self {
+            from: None,
+            ..Self::new("", to, conditions)
+        }
+    }
+}
+
+// Picks which clip an Animation plays. Game code sets parameters, and
+// transitions (checked in the order given, first one wins) move between
+// states based on them.
+pub struct Animator {
+    states: Vec<AnimState>,
+    transitions: Vec<AnimTransition>,
+    current: usize,
+    // the current state's clip hasn't been put on the Animation yet
+    entered: bool,
+    bools: BTreeMap<String, bool>,
+    floats: BTreeMap<String, f32>,
+    triggers: BTreeSet<String>,
+}
+
+impl Animator {
+    // Starts in the first state. Every state named by a fallback or a
+    // transition has to exist.
+    pub fn new(states: Vec<AnimState>, transitions: Vec<AnimTransition>) -> Self {
+        assert!(!states.is_empty(), "an animator needs at least one state");
+        let find = |name: &str| {
+            assert!(
+                states.iter().any(|s| s.name == name),
+                "no animation state named {:?}",
+                name
+            )
+        };
+        for state in states.iter() {
+            if let Some(fallback) = &state.fallback {
+                find(fallback);
+            }
+        }
+        for transition in transitions.iter() {
+            if let Some(from) = &transition.from {
+                find(from);
+            }
+            find(&transition.to);
+        }
+        Self {
+            states,
+            transitions,
+            current: 0,
+            entered: false,
+            bools: BTreeMap::new(),
+            floats: BTreeMap::new(),
+            triggers: BTreeSet::new(),
+        }
+    }
+
+    pub fn set_bool(&mut self, name: &str, value: bool) {
+        self.bools.insert(name.to_string(), value);
+    }
+
+    pub fn set_float(&mut self, name: &str, value: f32) {
+        self.floats.insert(name.to_string(), value);
+    }
+
+    // Stays set until a transition that checks it is taken
+    pub fn trigger(&mut self, name: &str) {
+        self.triggers.insert(name.to_string());
+    }
+
+    pub fn get_bool(&self, name: &str) -> bool {
+        self.bools.get(name).copied().unwrap_or(false)
+    }
+
+    pub fn get_float(&self, name: &str) -> f32 {
+        self.floats.get(name).copied().unwrap_or(0.0)
+    }
+
+    pub fn state(&self) -> &str {
+        &self.states[self.current].name
+    }
+
+    // Jumps straight to a state, ignoring transitions
+    pub fn set_state(&mut self, name: &str) {
+        self.current = self.index(name);
+        self.entered = false;
+    }
+
+    fn index(&self, name: &str) -> usize {
+        self.states
+            .iter()
+            .position(|s| s.name == name)
+            .unwrap_or_else(|| panic!("no animation state named {:?}", name))
+    }
+
+    fn holds(&self, condition: &Condition) -> bool {
+        match condition {
+            Condition::True(name) => self.get_bool(name),
+            Condition::False(name) => !self.get_bool(name),
+            Condition::Above(name, value) => self.get_float(name) > *value,
+            Condition::Below(name, value) => self.get_float(name) < *value,
+            Condition::Trigger(name) => self.triggers.contains(name),
+        }
+    }
+
+    // Takes whatever transition (or fallback) applies, then moves
+    // `animation` along by `dt` seconds. Check the animation's events
+    // afterwards as usual.
+    pub fn update(&mut self, animation: &mut Animation, dt: f32) {
+        if self.entered {
+            let current = self.state();
+            let taken = self.transitions.iter().position(|t| {
+                let from = match &t.from {
+                    Some(from) => from == current,
+                    None => {
+                        t.to != current
+                            || t.conditions
+                                .iter()
+                                .any(|c| matches!(c, Condition::Trigger(_)))
+                    }
+                };
+                from && t.exit_time.is_none_or(|exit| animation.progress() >= exit)
+                    && t.conditions.iter().all(|c| self.holds(c))
+            });
+            if let Some(taken) = taken {
+                for condition in self.transitions[taken].conditions.iter() {
+                    if let Condition::Trigger(name) = condition {
+                        self.triggers.remove(name);
+                    }
+                }
+                let to = self.transitions[taken].to.clone();
+                self.set_state(&to);
+            } else if animation.is_finished() {
+                if let Some(fallback) = self.states[self.current].fallback.clone() {
+                    self.set_state(&fallback);
+                }
+            }
+        }
+        if !self.entered {
+            let state = &self.states[self.current];
+            animation.set_animation(&state.clip, true);
+            animation.set_speed(state.speed);
+            self.entered = true;
+        }
+        animation.animate(dt);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animation::Playback;
+    use crate::objects::Rect;
+
+    // `count` frames a quarter second each; frame i is at x = first + i
+    fn clip(first: usize, count: usize, playback: Playback) -> Rc<AnimationData> {
+        let frames = (first..first + count)
+            .map(|i| (Rect::new(i as f32, 0.0, 1.0, 1.0), 0.25))
+            .collect();
+        Rc::new(AnimationData::new(frames, playback))
+    }
+
+    fn frame(anim: &Animation) -> usize {
+        anim.get_current_frame().x as usize
+    }
+
+    // idle is frames 0-1, run 10-11, jump 20-23 (played once)
+    fn animator(transitions: Vec<AnimTransition>) -> (Animator, Animation) {
+        let idle = clip(0, 2, Playback::Loop);
+        let states = vec![
+            AnimState::new("idle", &idle),
+            AnimState::new("run", &clip(10, 2, Playback::Loop)),
+            AnimState::new("jump", &clip(20, 4, Playback::Once)),
+        ];
+        (Animator::new(states, transitions), Animation::new(&idle))
+    }
+
+    #[test]
+    fn first_matching_transition_wins() {
+        let moving = || vec![Condition::True("moving".to_string())];
+        let (mut animator, mut anim) = animator(vec![
+            AnimTransition::new("idle", "run", moving()),
+            AnimTransition::new("idle", "jump", moving()),
+            AnimTransition::new(
+                "run",
+                "idle",
+                vec![Condition::Below("speed".to_string(), 1.0)],
+            ),
+        ]);
+        animator.update(&mut anim, 0.0);
+        assert_eq!(animator.state(), "idle");
+        animator.set_bool("moving", true);
+        animator.set_float("speed", 2.0);
+        animator.update(&mut anim, 0.0);
+        assert_eq!((animator.state(), frame(&anim)), ("run", 10));
+        animator.set_float("speed", 0.5);
+        animator.update(&mut anim, 0.0);
+        assert_eq!((animator.state(), frame(&anim)), ("idle", 0));
+    }
+
+    #[test]
+    fn triggers_last_until_their_transition_is_taken() {
+        let (mut animator, mut anim) = animator(vec![AnimTransition::new(
+            "idle",
+            "jump",
+            vec![
+                Condition::Trigger("jump".to_string()),
+                Condition::True("grounded".to_string()),
+            ],
+        )]);
+        animator.update(&mut anim, 0.0);
+        animator.trigger("jump");
+        animator.update(&mut anim, 0.0);
+        assert_eq!(animator.state(), "idle");
+        assert!(animator.triggers.contains("jump"));
+        animator.set_bool("grounded", true);
+        animator.update(&mut anim, 0.0);
+        assert_eq!(animator.state(), "jump");
+        assert!(animator.triggers.is_empty());
+    }
+
+    #[test]
+    fn exit_time_waits_for_progress() {
+        let mut to_idle = AnimTransition::new("jump", "idle", vec![]);
+        to_idle.exit_time = Some(0.5);
+        let (mut animator, mut anim) = animator(vec![to_idle]);
+        animator.set_state("jump");
+        animator.update(&mut anim, 0.25);
+        // transitions are checked before each update moves the clip along
+        animator.update(&mut anim, 0.25);
+        assert_eq!((animator.state(), anim.progress()), ("jump", 0.5));
+        animator.update(&mut anim, 0.0);
+        assert_eq!((animator.state(), frame(&anim)), ("idle", 0));
+    }
+
+    #[test]
+    fn finished_clips_fall_back() {
+        let idle = clip(0, 2, Playback::Loop);
+        let mut attack = AnimState::new("attack", &clip(30, 2, Playback::Times(2)));
+        attack.fallback = Some("idle".to_string());
+        let mut animator = Animator::new(vec![attack, AnimState::new("idle", &idle)], vec![]);
+        let mut anim = Animation::new(&idle);
+        animator.update(&mut anim, 0.0);
+        let mut shown = vec![];
+        for _ in 0..5 {
+            animator.update(&mut anim, 0.25);
+            shown.push(format!("{} {}", animator.state(), frame(&anim)));
+        }
+        // the fifth update finds the clip finished, goes back to idle and
+        // moves that along a frame
+        assert_eq!(
+            shown,
+            ["attack 31", "attack 30", "attack 31", "attack 31", "idle 1"]
+        );
+    }
+
+    #[test]
+    fn from_any_only_restarts_on_triggers() {
+        let (mut animator, mut anim) = animator(vec![
+            AnimTransition::from_any("jump", vec![Condition::True("airborne".to_string())]),
+            AnimTransition::from_any("jump", vec![Condition::Trigger("bounce".to_string())]),
+        ]);
+        animator.update(&mut anim, 0.0);
+        animator.set_bool("airborne", true);
+        animator.update(&mut anim, 0.0);
+        assert_eq!(frame(&anim), 20);
+        // staying airborne keeps the clip going
+        animator.update(&mut anim, 0.25);
+        animator.update(&mut anim, 0.25);
+        assert_eq!((animator.state(), frame(&anim)), ("jump", 22));
+        animator.trigger("bounce");
+        animator.set_bool("airborne", false);
+        animator.update(&mut anim, 0.0);
+        assert_eq!((animator.state(), frame(&anim)), ("jump", 20));
+    }
+
+    #[test]
+    #[should_panic(expected = "no animation state named \"fall\"")]
+    fn transitions_to_unknown_states_panic() {
+        animator(vec![AnimTransition::new("jump", "fall", vec![])]);
+    }
+
+    #[test]
+    #[should_panic(expected = "no animation state named \"fall\"")]
+    fn unknown_fallbacks_panic() {
+        let mut jump = AnimState::new("jump", &clip(20, 4, Playback::Once));
+        jump.fallback = Some("fall".to_string());
+        Animator::new(vec![jump], vec![]);
+    }
+}
@@ -0,0 +1,493 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+
+use crate::animation::AnimationData;
+use crate::objects::Rect;
+use crate::texture::Texture;
+
+#[derive(Debug)]
+pub enum AtlasError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Image(image::error::ImageError),
+    // Too big to pack, or a saved atlas that doesn't add up
+    Format(String),
+}
+
+impl fmt::Display for AtlasError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "couldn't read or write atlas: {}", e),
+            Self::Json(e) => write!(f, "bad atlas json: {}", e),
+            Self::Image(e) => write!(f, "couldn't load or save atlas image: {}", e),
+            Self::Format(e) => write!(f, "bad atlas: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for AtlasError {}
+
+impl From<std::io::Error> for AtlasError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for AtlasError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Json(e)
+    }
+}
+
+impl From<image::error::ImageError> for AtlasError {
+    fn from(e: image::error::ImageError) -> Self {
+        Self::Image(e)
+    }
+}
+
+fn format_err<T>(msg: impl Into<String>) -> Result<T, AtlasError> {
+    Err(AtlasError::Format(msg.into()))
+}
+
+// Packs rects into a fixed-size area, each one as low (then as far left)
+// as it goes, by keeping track of the outline of the rects placed so far.
+pub struct RectPacker {
+    width: u32,
+    height: u32,
+    // (x, y, width) of each flat stretch of the outline, left to right
+    skyline: Vec<(u32, u32, u32)>,
+}
+
+impl RectPacker {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            skyline: vec![(0, 0, width)],
+        }
+    }
+
+    // Where the top-left of a `w` by `h` rect went, if there was room
+    pub fn insert(&mut self, w: u32, h: u32) -> Option<(u32, u32)> {
+        if w == 0 || h == 0 {
+            return Some((0, 0));
+        }
+        let mut best: Option<(usize, u32)> = None;
+        for i in 0..self.skyline.len() {
+            if let Some(y) = self.fits(i, w, h) {
+                if best.is_none_or(|(_, best_y)| y < best_y) {
+                    best = Some((i, y));
+                }
+            }
+        }
+        let (i, y) = best?;
+        let x = self.skyline[i].0;
+        self.skyline.insert(i, (x, y + h, w));
+        // cut back whatever the new stretch covers
+        let right = x + w;
+        let next = i + 1;
+        while next < self.skyline.len() && self.skyline[next].0 < right {
+            let (sx, sy, sw) = self.skyline[next];
+            if sx + sw <= right {
+                self.skyline.remove(next);
+            } else {
+                self.skyline[next] = (right, sy, sx + sw - right);
+            }
+        }
+        // join up neighbours at the same height
+        self.skyline.dedup_by(|b, a| {
+            if a.1 == b.1 {
+                a.2 += b.2;
+                true
+            } else {
+                false
+            }
+        });
+        Some((x, y))
+    }
+
+    // How high a rect sitting at the start of stretch `i` would have to go
+    fn fits(&self, i: usize, w: u32, h: u32) -> Option<u32> {
+        let x = self.skyline[i].0;
+        if x + w > self.width {
+            return None;
+        }
+        let mut y = 0;
+        for &(sx, sy, _) in self.skyline[i..].iter() {
+            if sx >= x + w {
+                break;
+            }
+            y = y.max(sy);
+        }
+        (y + h <= self.height).then_some(y)
+    }
+
+    // How far down anything has been placed
+    pub fn used_height(&self) -> u32 {
+        self.skyline.iter().map(|&(_, y, _)| y).max().unwrap_or(0)
+    }
+}
+
+// Collects images to pack into one Atlas
+pub struct AtlasBuilder {
+    // Transparent pixels left between images
+    pub padding: u32,
+    // Repeats each image's edge pixels this far out, so scaled or
+    // rotated blits sampling just past an edge don't pick up a neighbour
+    pub extrude: u32,
+    // Neither side of the atlas gets bigger than this
+    pub max_size: u32,
+    images: Vec<(String, RgbaImage)>,
+}
+
+impl Default for AtlasBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AtlasBuilder {
+    pub fn new() -> Self {
+        Self {
+            padding: 0,
+            extrude: 0,
+            max_size: 4096,
+            images: vec![],
+        }
+    }
+
+    // A glyph sheet can go in whole; Atlas::sub_rect finds the glyphs after
+    pub fn add_image(&mut self, name: &str, image: RgbaImage) {
+        assert!(
+            self.images.iter().all(|(n, _)| n != name),
+            "atlas already has an image named {:?}",
+            name
+        );
+        self.images.push((name.to_string(), image));
+    }
+
+    pub fn add_file(&mut self, name: &str, path: &Path) -> Result<(), image::error::ImageError> {
+        self.add_image(name, image::open(path)?.into_rgba8());
+        Ok(())
+    }
+
+    pub fn build(&self) -> Result<Atlas, AtlasError> {
+        let border = 2 * self.extrude + self.padding;
+        let cells: Vec<(u32, u32)> = self
+            .images
+            .iter()
+            .map(|(_, img)| (img.width() + border, img.height() + border))
+            .collect();
+        // tallest first packs tightest
+        let mut order: Vec<usize> = (0..cells.len()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse((cells[i].1, cells[i].0)));
+
+        let area: u32 = cells.iter().map(|(w, h)| w * h).sum();
+        let widest = cells.iter().map(|&(w, _)| w).max().unwrap_or(1);
+        let mut width = widest.max((area as f32).sqrt() as u32).next_power_of_two();
+        let (spots, height) = loop {
+            if width > self.max_size {
+                return format_err(format!(
+                    "the images don't fit in {}x{}",
+                    self.max_size, self.max_size
+                ));
+            }
+            let mut packer = RectPacker::new(width, self.max_size);
+            let mut spots = vec![(0, 0); cells.len()];
+            let packed = order.iter().all(|&i| {
+                packer
+                    .insert(cells[i].0, cells[i].1)
+                    .map(|spot| spots[i] = spot)
+                    .is_some()
+            });
+            if packed {
+                break (spots, packer.used_height().max(1));
+            }
+            width *= 2;
+        };
+
+        let mut atlas = RgbaImage::new(width, height);
+        let mut rects = BTreeMap::new();
+        for ((name, img), (x, y)) in self.images.iter().zip(spots) {
+            let (w, h) = img.dimensions();
+            if w > 0 && h > 0 {
+                let e = self.extrude;
+                for ay in y..y + h + 2 * e {
+                    for ax in x..x + w + 2 * e {
+                        // clamping to the image repeats its edges
+                        let sx = (ax - x).saturating_sub(e).min(w - 1);
+                        let sy = (ay - y).saturating_sub(e).min(h - 1);
+                        atlas.put_pixel(ax, ay, *img.get_pixel(sx, sy));
+                    }
+                }
+            }
+            let (x, y) = (x + self.extrude, y + self.extrude);
+            rects.insert(
+                name.clone(),
+                Rect::new(x as f32, y as f32, w as f32, h as f32),
+            );
+        }
+        Ok(Atlas::new(atlas, rects))
+    }
+}
+
+// Lots of images in one texture, found by name
+pub struct Atlas {
+    pub texture: Rc<Texture>,
+    // kept around unpremultiplied for saving
+    image: RgbaImage,
+    rects: BTreeMap<String, Rect>,
+}
+
+impl Atlas {
+    fn new(image: RgbaImage, rects: BTreeMap<String, Rect>) -> Self {
+        Self {
+            texture: Rc::new(Texture::new(image.clone())),
+            image,
+            rects,
+        }
+    }
+
+    // Where an image went, ready for Screen::bitblt
+    pub fn rect(&self, name: &str) -> Option<Rect> {
+        self.rects.get(name).copied()
+    }
+
+    // `local` is a rect in the image as it was added (a glyph, a frame...);
+    // this is where it ended up. None if it isn't inside that image.
+    pub fn sub_rect(&self, name: &str, local: Rect) -> Option<Rect> {
+        let r = self.rect(name)?;
+        let inside = local.x >= 0.0
+            && local.y >= 0.0
+            && local.x + local.w <= r.w
+            && local.y + local.h <= r.h;
+        inside.then(|| Rect::new(r.x + local.x, r.y + local.y, local.w, local.h))
+    }
+
+    // An animation whose frames are cut from one image, moved into the atlas;
+    // None if any frame isn't inside that image
+    pub fn remap(&self, name: &str, data: &AnimationData) -> Option<AnimationData> {
+        let mut moved = data.clone();
+        for (frame, _) in moved.frames.iter_mut() {
+            *frame = self.sub_rect(name, *frame)?;
+        }
+        Some(moved)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.rects.keys().map(|name| name.as_str())
+    }
+
+    // Writes the image to `path` and the rects next to it as .json, in
+    // TexturePacker's format (so SpriteSheet::load reads it too)
+    pub fn save(&self, path: &Path) -> Result<(), AtlasError> {
+        self.image.save(path)?;
+        let image = match path.file_name().and_then(|n| n.to_str()) {
+            Some(name) => name.to_string(),
+            None => return format_err(format!("can't save an atlas to {:?}", path)),
+        };
+        let frames = self
+            .rects
+            .iter()
+            .map(|(name, r)| (name.clone(), saved::Frame::new(*r)))
+            .collect();
+        let saved = saved::Atlas {
+            frames,
+            meta: saved::Meta {
+                app: env!("CARGO_PKG_NAME").to_string(),
+                image,
+                size: saved::Size {
+                    w: self.image.width() as f32,
+                    h: self.image.height() as f32,
+                },
+            },
+        };
+        fs::write(
+            path.with_extension("json"),
+            serde_json::to_string_pretty(&saved)?,
+        )?;
+        Ok(())
+    }
+
+    // Loads what save() wrote; `path` is the image
+    pub fn load(path: &Path) -> Result<Self, AtlasError> {
+        let image = image::open(path)?.into_rgba8();
+        let text = fs::read_to_string(path.with_extension("json"))?;
+        let saved: saved::Atlas = serde_json::from_str(&text)?;
+        let (w, h) = (image.width() as f32, image.height() as f32);
+        let mut rects = BTreeMap::new();
+        for (name, frame) in saved.frames {
+            let r = frame.frame.rect();
+            if r.x < 0.0 || r.y < 0.0 || r.x + r.w > w || r.y + r.h > h {
+                return format_err(format!("{} is outside the atlas image", name));
+            }
+            rects.insert(name, r);
+        }
+        Ok(Self::new(image, rects))
+    }
+}
+
+// What save() writes, a subset of TexturePacker's JSON (hash) format
+mod saved {
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Atlas {
+        pub frames: BTreeMap<String, Frame>,
+        pub meta: Meta,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Frame {
+        pub frame: FrameRect,
+        #[serde(default)]
+        rotated: bool,
+        #[serde(default)]
+        trimmed: bool,
+    }
+
+    impl Frame {
+        pub fn new(r: Rect) -> Self {
+            Self {
+                frame: FrameRect {
+                    x: r.x,
+                    y: r.y,
+                    w: r.w,
+                    h: r.h,
+                },
+                rotated: false,
+                trimmed: false,
+            }
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct FrameRect {
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+    }
+
+    impl FrameRect {
+        pub fn rect(&self) -> Rect {
+            Rect::new(self.x, self.y, self.w, self.h)
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Meta {
+        #[serde(default)]
+        pub app: String,
+        pub image: String,
+        pub size: Size,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Size {
+        pub w: f32,
+        pub h: f32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn packed_rects_stay_inside_and_apart() {
+        let mut packer = RectPacker::new(64, 64);
+        let sizes = [
+            (20, 10),
+            (7, 30),
+            (33, 5),
+            (12, 12),
+            (40, 8),
+            (5, 5),
+            (9, 17),
+            (16, 3),
+        ];
+        let mut placed = vec![];
+        for &(w, h) in sizes.iter() {
+            let (x, y) = packer.insert(w, h).expect("room left");
+            assert!(x + w <= 64 && y + h <= 64);
+            placed.push((x, y, w, h));
+        }
+        for (i, a) in placed.iter().enumerate() {
+            for b in placed[i + 1..].iter() {
+                let apart =
+                    a.0 + a.2 <= b.0 || b.0 + b.2 <= a.0 || a.1 + a.3 <= b.1 || b.1 + b.3 <= a.1;
+                assert!(apart, "{:?} overlaps {:?}", a, b);
+            }
+        }
+        assert!(packer.used_height() <= 64);
+    }
+
+    #[test]
+    fn too_big_doesnt_pack() {
+        let mut packer = RectPacker::new(32, 32);
+        assert_eq!(packer.insert(33, 1), None);
+        assert_eq!(packer.insert(1, 33), None);
+        assert_eq!(packer.insert(32, 32), Some((0, 0)));
+        assert_eq!(packer.insert(1, 1), None);
+    }
+
+    fn solid(w: u32, h: u32, color: [u8; 4]) -> RgbaImage {
+        RgbaImage::from_pixel(w, h, Rgba(color))
+    }
+
+    #[test]
+    fn sub_rect_outside_the_image_is_none() {
+        let mut builder = AtlasBuilder::new();
+        builder.add_image("a", solid(8, 4, [255, 0, 0, 255]));
+        let atlas = builder.build().unwrap();
+        let r = atlas.rect("a").unwrap();
+        assert_eq!(
+            atlas.sub_rect("a", Rect::new(4.0, 0.0, 4.0, 4.0)),
+            Some(Rect::new(r.x + 4.0, r.y, 4.0, 4.0))
+        );
+        assert_eq!(atlas.sub_rect("a", Rect::new(5.0, 0.0, 4.0, 4.0)), None);
+        assert_eq!(atlas.sub_rect("a", Rect::new(-1.0, 0.0, 2.0, 2.0)), None);
+        assert_eq!(atlas.sub_rect("b", Rect::new(0.0, 0.0, 1.0, 1.0)), None);
+
+        let clip = AnimationData::new(
+            vec![
+                (Rect::new(0.0, 0.0, 4.0, 4.0), 0.1),
+                (Rect::new(6.0, 0.0, 4.0, 4.0), 0.1),
+            ],
+            crate::animation::Playback::Loop,
+        );
+        assert!(atlas.remap("a", &clip).is_none());
+    }
+
+    #[test]
+    fn saved_atlas_loads_back() {
+        let mut builder = AtlasBuilder::new();
+        builder.padding = 1;
+        builder.extrude = 1;
+        builder.add_image("red", solid(10, 6, [255, 0, 0, 255]));
+        builder.add_image("half", solid(3, 9, [0, 255, 0, 128]));
+        builder.add_image("blue", solid(5, 5, [0, 0, 255, 255]));
+        let atlas = builder.build().unwrap();
+
+        let dir = std::env::temp_dir().join(format!("engine2d-atlas-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("atlas.png");
+        atlas.save(&path).unwrap();
+        let loaded = Atlas::load(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(atlas.names().eq(loaded.names()));
+        for name in atlas.names() {
+            assert_eq!(atlas.rect(name), loaded.rect(name));
+        }
+        assert_eq!(atlas.image, loaded.image);
+    }
+}
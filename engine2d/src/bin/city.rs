@@ -0,0 +1,117 @@
+// Walks a box around content/city.tmx, a Tiled map cut from tileset2.png.
+// The right half of the city is the left half drawn with flipped tiles; the
+// ground and rooftops are invisible layers marked solid and one-way.
+use engine2d::{
+    camera::Camera,
+    collision,
+    game::{self, Config, Game},
+    input::Input,
+    objects::{Color, MovingRect, Rect, Vec2},
+    screen::Screen,
+    tilemap::{DrawTilemapExt, Layer, Tilemap},
+};
+use std::path::Path;
+use winit::event::VirtualKeyCode;
+const WIDTH: usize = 240;
+const HEIGHT: usize = 360;
+const CLEAR_COL: Color = [120, 170, 255, 255];
+const PLAYER_COL: Color = [255, 128, 128, 255];
+const GOAL_COL: Color = [255, 255, 0, 160];
+const WALK_SPEED: f32 = 1.5;
+const JUMP_SPEED: f32 = 6.5;
+const GRAVITY: f32 = 0.25;
+const MAX_FALL: f32 = 6.0;
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum ActionID {
+    Left,
+    Right,
+    Jump,
+}
+
+struct GameState {
+    map: Tilemap,
+    spawn: Vec2,
+    goal: Rect,
+    player: MovingRect,
+    on_ground: bool,
+}
+
+impl GameState {
+    fn respawn(&mut self) {
+        self.player = MovingRect::new(
+            self.spawn.x - 5.0,
+            self.spawn.y - 14.0,
+            10.0,
+            14.0,
+            Vec2::zero(),
+        );
+    }
+}
+
+impl Game for GameState {
+    type ActionID = ActionID;
+
+    fn update(&mut self, input: &Input<ActionID>) {
+        let player = &mut self.player;
+        player.vel.x = 0.0;
+        if input.is_held(ActionID::Left) {
+            player.vel.x -= WALK_SPEED;
+        }
+        if input.is_held(ActionID::Right) {
+            player.vel.x += WALK_SPEED;
+        }
+        if self.on_ground && input.is_pressed(ActionID::Jump) {
+            player.vel.y = -JUMP_SPEED;
+        }
+        player.vel.y = (player.vel.y + GRAVITY).min(MAX_FALL);
+        self.on_ground = collision::move_against_tiles(player, &self.map).bottom;
+        // keep to the map sideways
+        let right = self.map.pixel_size().x - player.w;
+        player.x = player.x.clamp(0.0, right);
+        if player.as_rect().intersects(self.goal) || player.y > self.map.pixel_size().y {
+            self.respawn();
+        }
+    }
+
+    fn draw(&self, screen: &mut Screen, _alpha: f32) {
+        screen.clear(CLEAR_COL);
+        let center = self.player.x + self.player.w / 2.0;
+        let scroll =
+            (center - WIDTH as f32 / 2.0).clamp(0.0, self.map.pixel_size().x - WIDTH as f32);
+        screen.set_camera(Camera::new(Vec2::new(scroll, 0.0), 1.0));
+        screen.draw_tilemap(&self.map);
+        screen.rect_lines(self.goal, GOAL_COL);
+        screen.rect(self.player.as_rect(), PLAYER_COL);
+    }
+}
+
+fn main() {
+    let map = Tilemap::load(Path::new("content/city.tmx")).unwrap();
+    let objects = match map.layer_named("objects") {
+        Some(Layer::Objects(layer)) => &layer.objects,
+        _ => panic!("city.tmx has no objects layer"),
+    };
+    let find = |name: &str| {
+        objects
+            .iter()
+            .find(|o| o.name == name)
+            .unwrap_or_else(|| panic!("city.tmx has no {} object", name))
+            .rect
+    };
+    let (spawn, goal) = (find("spawn").pos(), find("goal"));
+
+    let mut input = Input::new();
+    input.add_key_to_map(ActionID::Left, VirtualKeyCode::Left);
+    input.add_key_to_map(ActionID::Right, VirtualKeyCode::Right);
+    input.add_key_to_map(ActionID::Jump, VirtualKeyCode::Up);
+    let mut state = GameState {
+        map,
+        spawn,
+        goal,
+        player: MovingRect::new(0.0, 0.0, 0.0, 0.0, Vec2::zero()),
+        on_ground: false,
+    };
+    state.respawn();
+    game::run(Config::new("City", WIDTH, HEIGHT), input, state);
+}
@@ -13,7 +13,7 @@ impl Obstacles {
     pub fn generate_obstacles(&self) -> (i32, i32) {
         let freq_total: usize = self.frequency_values.iter().sum();
         let mut rng = rand::thread_rng();
-        let mut x: i32 = rng.gen_range((0 as i32)..(freq_total as i32));
+        let mut x: i32 = rng.gen_range(0..(freq_total as i32));
 
         for (f_vals, obs) in self.frequency_values.iter().zip(self.obstacles.iter()) {
             x -= *f_vals as i32;
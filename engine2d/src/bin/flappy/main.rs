@@ -2,16 +2,11 @@ use std::fs::File;
 use std::io::BufReader;
 use std::path::Path;
 use std::rc::Rc;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
-use pixels::{Pixels, SurfaceTexture};
 use rand::prelude::*;
 use rodio::{OutputStreamHandle, Source};
-use winit::dpi::LogicalSize;
-use winit::event::{Event, VirtualKeyCode};
-use winit::event_loop::{ControlFlow, EventLoop};
-use winit::window::WindowBuilder;
-use winit_input_helper::WinitInputHelper;
+use winit::event::VirtualKeyCode;
 
 mod background;
 mod generation;
@@ -19,134 +14,154 @@ mod generation;
 use background::Background;
 
 use engine2d::{
-    animation::{Animation, AnimationData},
-    collision, input,
+    animation::Animation,
+    animator::{AnimState, AnimTransition, Animator, Condition},
+    collision::{self, HasLayers, Layers},
+    game::{self, Config},
+    input::{self, Input},
     objects::*,
-    screen::Screen,
-    sprite::{DrawSpriteExt, Sprite},
+    scene::{self, Fade, Scene, SceneStack},
+    screen::{BlendMode, Screen},
+    shape::{self, Circle, Shape},
+    sprite::{DrawSpriteExt, NodeId, Sprite, SpriteGraph},
+    spritesheet::SpriteSheet,
     text::{self, DrawTextExt},
     texture::Texture,
+    transform::Transform2D,
 };
 
 const DT: f64 = 1.0 / 60.0;
 const WIDTH: usize = 240;
 const HEIGHT: usize = 360;
-const DEPTH: usize = 4;
 const CHAR_SIZE: f32 = 16.0;
-
-#[derive(Debug)]
-enum Mode {
-    Title,
-    Play,
-    EndGame,
+// the pigeon is round, so only a circle inside its box counts for hits
+const PIGEON_RADIUS: f32 = 8.0;
+
+// Lives across scenes
+struct Shared {
+    rsrc: Resources,
+    stream_handle: OutputStreamHandle,
+    // the last round's score
+    score: u32,
 }
 
-struct GameState {
+struct Title;
+
+struct Play {
     player: MovingRect,
-    player_sprite: Sprite,
-    holding: Holding,
+    sprites: SpriteGraph,
+    pigeon: NodeId,
+    pigeon_animator: Animator,
     obstacles: Vec<Rect>,
     obstacle_data: Vec<ObstacleData>,
+    // the gaps between pipe pairs; flying through one scores a point
+    gates: Vec<Rect>,
     move_vel: f32,
     background: Background,
-    last_flap_noise: Instant,
     score: u32,
     time_between: u32,
-    mode: Mode,
+    // ticks since the last pair of pipes went in
+    pipe_timer: u32,
+    generate: generation::Obstacles,
 }
 
+// Drawn over the frozen Play scene
+struct Paused;
+
+struct GameOver;
+
+type Transition = scene::Transition<Shared, ActionID>;
+
+const FADE: Fade = Fade {
+    color: [0, 0, 0, 255],
+    ticks: 20,
+};
+
 pub struct Resources {
-    pub animation_data: Vec<Rc<AnimationData>>,
+    pub pigeon: SpriteSheet,
+    pub buildings: SpriteSheet,
     pub text_info: text::TextInfo,
-    pub textures: Vec<Rc<Texture>>,
 }
 
 struct ObstacleData {
     filled: bool,
-    passed: bool,
 }
 
-#[derive(PartialEq, Eq, PartialOrd, Ord)]
-enum ActionID {
-    Flap,
+// What a collider belongs to; pipes come in top/bottom pairs
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Body {
+    Player,
+    Pipe(usize),
+    Gate(usize),
 }
 
-enum Holding {
-    Worm(Sprite),
-    Flower(Sprite),
-    Letter(Sprite),
-}
+const PLAYER_LAYER: u32 = 1;
+const PIPE_LAYER: u32 = 2;
+const GATE_LAYER: u32 = 4;
 
-impl Holding {
-    fn random(rsrc: &Resources) -> Self {
-        let mut rng = thread_rng();
-        match rng.gen_range(0..3) {
-            0 => Self::Flower(Sprite::new(
-                &Rc::clone(&rsrc.textures[0]),
-                Animation::new(&rsrc.animation_data[7]),
-                Vec2::new(45.0, HEIGHT as f32 / 2.0 - 10.0),
-            )),
-            1 => Self::Worm(Sprite::new(
-                &Rc::clone(&rsrc.textures[0]),
-                Animation::new(&rsrc.animation_data[8]),
-                Vec2::new(47.0, HEIGHT as f32 / 2.0 - 9.0),
-            )),
-            2 => Self::Letter(Sprite::new(
-                &Rc::clone(&rsrc.textures[0]),
-                Animation::new(&rsrc.animation_data[9]),
-                Vec2::new(46.0, HEIGHT as f32 / 2.0 - 9.0),
-            )),
-            _ => panic!("unreachable"),
-        }
-    }
-
-    fn get_sprite(&self) -> &Sprite {
+impl HasLayers for Body {
+    fn layers(&self) -> Layers {
         match self {
-            Self::Flower(sprite) => sprite,
-            Self::Letter(sprite) => sprite,
-            Self::Worm(sprite) => sprite,
+            Body::Player => Layers::new(PLAYER_LAYER, PIPE_LAYER | GATE_LAYER),
+            Body::Pipe(_) => Layers::new(PIPE_LAYER, PLAYER_LAYER),
+            Body::Gate(_) => Layers::trigger(GATE_LAYER, PLAYER_LAYER),
         }
     }
+}
 
-    fn get_sprite_mut(&mut self) -> &mut Sprite {
-        match self {
-            Self::Flower(sprite) => sprite,
-            Self::Letter(sprite) => sprite,
-            Self::Worm(sprite) => sprite,
-        }
-    }
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum ActionID {
+    Flap,
+    Start,
+    Pause,
+}
 
-    fn draw(&self, screen: &mut Screen) {
-        screen.draw_sprite(self.get_sprite());
-    }
+// Gives the pigeon a random worm, flower or letter to carry. It's a child of
+// the pigeon's node so it follows along and tilts with it.
+fn hold_random(rsrc: &Resources, sprites: &mut SpriteGraph, pigeon: NodeId) {
+    // `offset` is from the pigeon's pivot to the item's top-left
+    let (item, offset) = match thread_rng().gen_range(0..3) {
+        0 => ("flower", Vec2::new(5.0, -8.5)),
+        1 => ("worm", Vec2::new(7.0, -7.5)),
+        2 => ("letter", Vec2::new(6.0, -7.5)),
+        _ => panic!("unreachable"),
+    };
+    sprites.add(
+        Sprite::new(
+            &rsrc.pigeon.texture,
+            Animation::new(rsrc.pigeon.clip(item).unwrap()),
+            Vec2::zero(),
+        ),
+        Transform2D::from_translation(offset),
+        Some(pigeon),
+    );
+}
+
+// Flaps when told to, and glides once it starts falling
+fn pigeon_animator(rsrc: &Resources) -> Animator {
+    Animator::new(
+        vec![
+            AnimState::new("glide", rsrc.pigeon.clip("glide").unwrap()),
+            AnimState::new("flap", rsrc.pigeon.clip("flap").unwrap()),
+        ],
+        vec![
+            AnimTransition::from_any("flap", vec![Condition::Trigger("flap".to_string())]),
+            AnimTransition::new(
+                "flap",
+                "glide",
+                vec![Condition::Below("rise".to_string(), 0.0)],
+            ),
+        ],
+    )
+}
+
+// Where the middle of the pigeon starts out
+fn pigeon_start() -> Vec2 {
+    Vec2::new(40.0, HEIGHT as f32 / 2.0 - 1.5)
 }
 
 fn main() {
     let rsrc = Resources::new();
-    let mut state = GameState {
-        player: MovingRect::new(
-            30.0,
-            HEIGHT as f32 / 2.0 - 13.0,
-            13.0,
-            20.0,
-            Vec2::new(0.0, 0.0),
-        ),
-        player_sprite: Sprite::new(
-            &Rc::clone(&rsrc.textures[0]),
-            Animation::new(&rsrc.animation_data[0]),
-            Vec2::new(30.0, HEIGHT as f32 / 2.0 - 10.0),
-        ),
-        holding: Holding::random(&rsrc),
-        background: Background::new(&rsrc),
-        obstacles: Vec::new(),
-        obstacle_data: Vec::new(),
-        score: 0,
-        move_vel: 1.0,
-        time_between: 3000,
-        mode: Mode::Title,
-        last_flap_noise: Instant::now(),
-    };
-    let mut rng = thread_rng();
 
     let (_stream, stream_handle) = rodio::OutputStream::try_default().unwrap();
     play_coo(&stream_handle);
@@ -160,307 +175,281 @@ fn main() {
 
     let _ = stream_handle.play_raw(background.convert_samples());
 
-    let event_loop = EventLoop::new();
-    let mut input_events = WinitInputHelper::new();
-    let generate = generation::Obstacles {
-        obstacles: vec![(80, 120), (160, 130), (70, 230)],
-        frequency_values: vec![1, 1, 1],
+    let shared = Shared {
+        rsrc,
+        stream_handle,
+        score: 0,
     };
 
     let mut input = input::Input::new();
     input.add_key_to_map(ActionID::Flap, VirtualKeyCode::Space);
+    input.add_key_to_map(ActionID::Start, VirtualKeyCode::Return);
+    input.add_key_to_map(ActionID::Pause, VirtualKeyCode::P);
 
-    let window = {
-        let size = LogicalSize::new(WIDTH as f64 * 2.0, HEIGHT as f64 * 2.0);
-        WindowBuilder::new()
-            .with_title("flappy bird")
-            .with_inner_size(size)
-            .with_min_inner_size(size)
-            .with_resizable(false)
-            .build(&event_loop)
-            .unwrap()
-    };
-    let mut pixels = {
-        let window_size = window.inner_size();
-        let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
-        Pixels::new(WIDTH as u32, HEIGHT as u32, surface_texture).unwrap()
+    let config = Config {
+        scale: 2.0,
+        tick_rate: 1.0 / DT,
+        ..Config::new("flappy bird", WIDTH, HEIGHT)
     };
+    game::run(config, input, SceneStack::new(shared, Title));
+}
 
-    let mut last_added_rect = Instant::now();
-    let mut available_time = 0.0;
-    let mut since = Instant::now();
-
-    event_loop.run(move |event, _, control_flow| {
-        match state.mode {
-            Mode::Title => {
-                // Draw the current frame
-                if let Event::RedrawRequested(_) = event {
-                    let mut screen = Screen::wrap(pixels.get_frame(), WIDTH, HEIGHT, DEPTH);
-                    screen.clear([135, 206, 250, 150]);
-
-                    screen.draw_text_at_pos(
-                        format!("score: {}", state.score).as_str(),
-                        Vec2::new(0.0, 0.0),
-                        &rsrc.text_info,
-                    );
-                    screen.draw_text_at_pos(
-                        "flappy pigeon",
-                        Vec2::new(20.0, 60.0),
-                        &rsrc.text_info,
-                    );
-                    screen.draw_text_at_pos("press space", Vec2::new(40.0, 190.0), &rsrc.text_info);
-                    screen.draw_text_at_pos("to flap", Vec2::new(73.0, 210.0), &rsrc.text_info);
-                    screen.draw_text_at_pos("press enter", Vec2::new(40.0, 240.0), &rsrc.text_info);
-                    screen.draw_text_at_pos("to start", Vec2::new(65.0, 260.0), &rsrc.text_info);
-
-                    if pixels.render().is_err() {
-                        *control_flow = ControlFlow::Exit;
-                        return;
-                    }
+fn draw_score(screen: &mut Screen, rsrc: &Resources, score: u32) {
+    screen.draw_text_at_pos(
+        format!("score: {}", score).as_str(),
+        Vec2::new(0.0, 0.0),
+        &rsrc.text_info,
+    );
+}
 
-                    //available_time += since.elapsed().as_secs_f64();
-                }
-                // Handle input_events events
-                if input_events.update(&event) {
-                    // Close events
-                    if input_events.key_pressed(VirtualKeyCode::Escape) || input_events.quit() {
-                        *control_flow = ControlFlow::Exit;
-                        return;
-                    }
-                    if input_events.key_pressed(VirtualKeyCode::Return) {
-                        state.mode = Mode::Play;
-                        state.last_flap_noise = Instant::now();
-                        state.player.x = 30.0;
-                        state.player.y = HEIGHT as f32 / 2.0 - 10.0;
-                        state.player.vel = Vec2::new(0.0, 0.0);
-                        state.time_between = 3000;
-                        state.move_vel = 1.0;
-                        last_added_rect = Instant::now();
-                        since = Instant::now();
-                    }
-                    // Resize the window
-                    if let Some(size) = input_events.window_resized() {
-                        pixels.resize(size.width, size.height);
-                    }
-                }
-            }
-            Mode::Play => {
-                // Draw the current frame
-                if let Event::RedrawRequested(_) = event {
-                    state.player_sprite.animation.animate();
-                    let mut screen = Screen::wrap(pixels.get_frame(), WIDTH, HEIGHT, DEPTH);
-                    state.background.draw(&mut screen);
-
-                    screen.draw_sprite(&state.player_sprite);
-                    state.holding.draw(&mut screen);
-
-                    // draw state.obstacles
-                    for (obstacle, data) in state.obstacles.iter().zip(state.obstacle_data.iter()) {
-                        if data.filled {
-                            screen.rect(*obstacle, [255, 0, 0, 255]);
-                        } else {
-                            screen.rect_lines(*obstacle, [255, 0, 0, 255]);
-                        }
-                    }
+impl Scene<Shared, ActionID> for Title {
+    fn update(&mut self, shared: &mut Shared, input: &Input<ActionID>) -> Transition {
+        if input.is_pressed(ActionID::Start) {
+            Transition::replace(Play::new(&shared.rsrc)).with_fade(FADE)
+        } else {
+            Transition::None
+        }
+    }
 
-                    screen.draw_text_at_pos(
-                        format!("score: {}", state.score).as_str(),
-                        Vec2::new(0.0, 0.0),
-                        &rsrc.text_info,
-                    );
+    fn draw(&self, shared: &Shared, screen: &mut Screen, _alpha: f32) {
+        let rsrc = &shared.rsrc;
+        screen.clear([135, 206, 250, 150]);
 
-                    if pixels.render().is_err() {
-                        *control_flow = ControlFlow::Exit;
-                        return;
-                    }
+        draw_score(screen, rsrc, shared.score);
+        screen.draw_text_at_pos("flappy pigeon", Vec2::new(20.0, 60.0), &rsrc.text_info);
+        screen.draw_text_at_pos("press space", Vec2::new(40.0, 190.0), &rsrc.text_info);
+        screen.draw_text_at_pos("to flap", Vec2::new(73.0, 210.0), &rsrc.text_info);
+        screen.draw_text_at_pos("press enter", Vec2::new(40.0, 240.0), &rsrc.text_info);
+        screen.draw_text_at_pos("to start", Vec2::new(65.0, 260.0), &rsrc.text_info);
+    }
+}
 
-                    available_time += since.elapsed().as_secs_f64();
-                }
+impl Scene<Shared, ActionID> for Paused {
+    fn update(&mut self, _shared: &mut Shared, input: &Input<ActionID>) -> Transition {
+        if input.is_pressed(ActionID::Pause) {
+            Transition::Pop
+        } else {
+            Transition::None
+        }
+    }
 
-                // Handle input_events events
-                if input_events.update(&event) {
-                    // Close events
-                    if input_events.key_pressed(VirtualKeyCode::Escape) || input_events.quit() {
-                        *control_flow = ControlFlow::Exit;
-                        return;
-                    }
+    fn draw(&self, shared: &Shared, screen: &mut Screen, _alpha: f32) {
+        let rsrc = &shared.rsrc;
+        let mode = screen.blend_mode();
+        screen.set_blend_mode(BlendMode::Over);
+        screen.rect(
+            Rect::new(0.0, 0.0, WIDTH as f32, HEIGHT as f32),
+            [0, 0, 0, 128],
+        );
+        screen.set_blend_mode(mode);
+        screen.draw_text_at_pos("paused", Vec2::new(72.0, 160.0), &rsrc.text_info);
+        screen.draw_text_at_pos("press p", Vec2::new(64.0, 190.0), &rsrc.text_info);
+    }
 
-                    // Resize the window
-                    if let Some(size) = input_events.window_resized() {
-                        pixels.resize(size.width, size.height);
-                    }
-                }
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
 
-                while available_time >= DT {
-                    since = Instant::now();
-                    available_time -= DT;
+impl Scene<Shared, ActionID> for GameOver {
+    fn update(&mut self, shared: &mut Shared, input: &Input<ActionID>) -> Transition {
+        if input.is_pressed(ActionID::Start) {
+            Transition::replace(Play::new(&shared.rsrc)).with_fade(FADE)
+        } else {
+            Transition::None
+        }
+    }
 
-                    if state.last_flap_noise.elapsed() > Duration::from_secs(9) {
-                        play_flap(&stream_handle);
-                        state.last_flap_noise = Instant::now();
-                    }
+    fn draw(&self, shared: &Shared, screen: &mut Screen, _alpha: f32) {
+        let rsrc = &shared.rsrc;
+        screen.clear([255, 150, 0, 255]);
 
-                    state.background.update(&rsrc);
-                    input.update(&input_events);
-                    if input.is_pressed(ActionID::Flap) {
-                        state.player.vel.y = 2.0;
-                        state
-                            .player_sprite
-                            .set_animation(&rsrc.animation_data[1], true);
-                    }
+        draw_score(screen, rsrc, shared.score);
 
-                    // update velocity for bird
-                    state.player.vel.y -= 0.04;
-                    if state.player.vel.y < 0.0 {
-                        state
-                            .player_sprite
-                            .set_animation(&rsrc.animation_data[0], true);
-                    }
+        screen.draw_text_at_pos("game over!!!", Vec2::new(20.0, 60.0), &rsrc.text_info);
 
-                    // update position
-                    state.player.x -= state.player.vel.x;
-                    state.player.y -= state.player.vel.y;
-                    state.player_sprite.position.x -= state.player.vel.x;
-                    state.player_sprite.position.y -= state.player.vel.y;
-                    state.holding.get_sprite_mut().position.x -= state.player.vel.x;
-                    state.holding.get_sprite_mut().position.y -= state.player.vel.y;
+        screen.draw_text_at_pos("press enter", Vec2::new(40.0, 240.0), &rsrc.text_info);
+        screen.draw_text_at_pos("to try again", Vec2::new(30.0, 260.0), &rsrc.text_info);
+    }
+}
 
-                    for obstacle in state.obstacles.iter_mut() {
-                        obstacle.x -= state.move_vel;
-                    }
+impl Scene<Shared, ActionID> for Play {
+    fn update(&mut self, shared: &mut Shared, input: &Input<ActionID>) -> Transition {
+        if input.is_pressed(ActionID::Pause) {
+            return Transition::push(Paused);
+        }
+        self.background.update();
+        if input.is_pressed(ActionID::Flap) {
+            self.player.vel.y = 2.0;
+            self.pigeon_animator.trigger("flap");
+        }
 
-                    let contacts = collision::gather_contacts(&state.player, &state.obstacles);
-                    for contact in contacts.iter() {
-                        use collision::ContactID;
-                        if let (ContactID::Player, ContactID::Obstacle) = contact.get_ids() {
-                            // TODO: have a function that resets the game state??
-                            play_coo(&stream_handle);
-                            state.mode = Mode::EndGame;
-                        }
-                    }
+        // update velocity for bird
+        self.player.vel.y -= 0.04;
+        self.pigeon_animator.set_float("rise", self.player.vel.y);
+        let animation = &mut self.sprites.sprite_mut(self.pigeon).animation;
+        self.pigeon_animator.update(animation, DT as f32);
+        if animation.has_event("flap") {
+            play_flap(&shared.stream_handle);
+        }
+        // nose up while rising, nose down while falling
+        self.sprites.local_mut(self.pigeon).rotation = (-self.player.vel.y * 0.15).clamp(-0.4, 0.6);
 
-                    if state.obstacles.len() >= 2
-                        && state.obstacles[0].x + state.obstacles[0].w <= 0.0
-                    {
-                        // remove the first two state.obstacles
-                        state.obstacles.remove(0);
-                        state.obstacles.remove(0);
-                        state.obstacle_data.remove(0);
-                        state.obstacle_data.remove(0);
-                    }
+        // update position
+        let step = -self.player.vel;
+        self.player.translate(step);
+        self.sprites.local_mut(self.pigeon).translation += step;
+        self.sprites.update();
 
-                    if since.duration_since(last_added_rect)
-                        >= Duration::from_millis(state.time_between as u64)
-                    {
-                        let (top, bottom) = generate.generate_obstacles();
-                        state
-                            .obstacles
-                            .push(Rect::new(WIDTH as f32, 0.0, 20.0, top as f32));
-                        state.obstacles.push(Rect::new(
-                            WIDTH as f32,
-                            HEIGHT as f32 - bottom as f32,
-                            20.0,
-                            bottom as f32,
-                        ));
-                        state.obstacle_data.push(ObstacleData {
-                            passed: false,
-                            filled: rng.gen_bool(0.8),
-                        });
-                        state.obstacle_data.push(ObstacleData {
-                            passed: false,
-                            filled: rng.gen_bool(0.8),
-                        });
-                        last_added_rect = Instant::now();
-                        since = Instant::now();
-                    }
+        for obstacle in self.obstacles.iter_mut().chain(self.gates.iter_mut()) {
+            obstacle.x -= self.move_vel;
+        }
 
-                    for (i, (obst, data)) in state
-                        .obstacles
-                        .iter_mut()
-                        .zip(state.obstacle_data.iter_mut())
-                        .enumerate()
-                    {
-                        if state.player.x > obst.x && !data.passed {
-                            data.passed = true;
-                            if i % 2 == 0 {
-                                // let _ = stream_handle.play_raw(coo.convert_samples());
-                                state.score += 1;
-                                if state.move_vel < 3.0 {
-                                    state.move_vel *= 1.1;
-                                }
-                                if state.obstacles.len() >= 4
-                                    && state.obstacles[state.obstacles.len() - 1].x
-                                        - state.obstacles[state.obstacles.len() - 3].x
-                                        > state.obstacles[state.obstacles.len() - 3].w * 2.0
-                                {
-                                    state.time_between = (state.time_between - 200).max(800);
-                                }
-                                break;
-                            }
-                        }
+        let colliders: Vec<Rect> = self
+            .obstacles
+            .iter()
+            .chain(self.gates.iter())
+            .copied()
+            .collect();
+        let ids: Vec<Body> = (0..self.obstacles.len())
+            .map(|i| Body::Pipe(i / 2))
+            .chain((0..self.gates.len()).map(Body::Gate))
+            .collect();
+        let contacts =
+            collision::gather_layered_contacts(&[self.player], &[Body::Player], &colliders, &ids);
+        let pigeon = Shape::Circle(Circle::new(
+            self.sprites.world(self.pigeon).translation,
+            PIGEON_RADIUS,
+        ));
+        let mut scored = None;
+        for contact in contacts.iter() {
+            match contact.get_ids() {
+                (Body::Player, Body::Pipe(_)) => {
+                    let pipe = Shape::Rect(colliders[contact.b_index]);
+                    if !shape::shapes_overlap(&pigeon, &pipe) {
+                        continue;
                     }
+                    play_coo(&shared.stream_handle);
+                    shared.score = self.score;
+                    return Transition::replace(GameOver);
                 }
+                (Body::Player, Body::Gate(gate)) => scored = Some(gate),
+                _ => {}
             }
-            Mode::EndGame => {
-                if let Event::RedrawRequested(_) = event {
-                    let mut screen = Screen::wrap(pixels.get_frame(), WIDTH, HEIGHT, DEPTH);
-                    screen.clear([255, 150, 0, 255]);
-
-                    screen.draw_text_at_pos(
-                        format!("score: {}", state.score).as_str(),
-                        Vec2::new(0.0, 0.0),
-                        &rsrc.text_info,
-                    );
-
-                    screen.draw_text_at_pos("game over!!!", Vec2::new(20.0, 60.0), &rsrc.text_info);
-
-                    screen.draw_text_at_pos("press enter", Vec2::new(40.0, 240.0), &rsrc.text_info);
-                    screen.draw_text_at_pos(
-                        "to try again",
-                        Vec2::new(30.0, 260.0),
-                        &rsrc.text_info,
-                    );
-
-                    if pixels.render().is_err() {
-                        *control_flow = ControlFlow::Exit;
-                        return;
-                    }
-                }
+        }
+        if let Some(gate) = scored {
+            // the gate goes away so it only scores once
+            self.gates.remove(gate);
+            self.score += 1;
+            if self.move_vel < 3.0 {
+                self.move_vel *= 1.1;
+            }
+            if self.obstacles.len() >= 4
+                && self.obstacles[self.obstacles.len() - 1].x
+                    - self.obstacles[self.obstacles.len() - 3].x
+                    > self.obstacles[self.obstacles.len() - 3].w * 2.0
+            {
+                self.time_between = (self.time_between - 200).max(800);
+            }
+        }
 
-                if input_events.update(&event) {
-                    // Close events
-                    if input_events.key_pressed(VirtualKeyCode::Escape) || input_events.quit() {
-                        *control_flow = ControlFlow::Exit;
-                        return;
-                    }
-                    if input_events.key_pressed(VirtualKeyCode::Return) {
-                        state.mode = Mode::Play;
-                        state.player.x = 30.0;
-                        state.player.y = HEIGHT as f32 / 2.0 - 13.0;
-                        state.player_sprite.position.x = 30.0;
-                        state.player_sprite.position.y = HEIGHT as f32 / 2.0 - 10.0;
-                        state.player.vel = Vec2::new(0.0, 0.0);
-                        state.background.clear(&rsrc);
-                        state.obstacles.clear();
-                        state.last_flap_noise = Instant::now();
-                        state.obstacle_data.clear();
-                        state.time_between = 3000;
-                        state.move_vel = 1.0;
-                        state.score = 0;
-                        state.holding = Holding::random(&rsrc);
-                        last_added_rect = Instant::now();
-                        since = Instant::now();
-                        //return;
-                    }
-                    // Resize the window
-                    if let Some(size) = input_events.window_resized() {
-                        pixels.resize(size.width, size.height);
-                    }
-                }
+        if self.obstacles.len() >= 2 && self.obstacles[0].x + self.obstacles[0].w <= 0.0 {
+            // remove the first two self.obstacles
+            self.obstacles.remove(0);
+            self.obstacles.remove(0);
+            self.obstacle_data.remove(0);
+            self.obstacle_data.remove(0);
+        }
+        self.gates.retain(|gate| gate.x + gate.w > 0.0);
+
+        self.pipe_timer += 1;
+        if self.pipe_timer as f64 * DT * 1000.0 >= self.time_between as f64 {
+            let mut rng = thread_rng();
+            let (top, bottom) = self.generate.generate_obstacles();
+            self.obstacles
+                .push(Rect::new(WIDTH as f32, 0.0, 20.0, top as f32));
+            self.obstacles.push(Rect::new(
+                WIDTH as f32,
+                HEIGHT as f32 - bottom as f32,
+                20.0,
+                bottom as f32,
+            ));
+            self.gates.push(Rect::new(
+                WIDTH as f32,
+                top as f32,
+                20.0,
+                HEIGHT as f32 - bottom as f32 - top as f32,
+            ));
+            self.obstacle_data.push(ObstacleData {
+                filled: rng.gen_bool(0.8),
+            });
+            self.obstacle_data.push(ObstacleData {
+                filled: rng.gen_bool(0.8),
+            });
+            self.pipe_timer = 0;
+        }
+        Transition::None
+    }
+
+    fn draw(&self, shared: &Shared, screen: &mut Screen, _alpha: f32) {
+        self.background.draw(screen);
+
+        screen.draw_sprite_graph(&self.sprites);
+
+        // draw self.obstacles
+        for (obstacle, data) in self.obstacles.iter().zip(self.obstacle_data.iter()) {
+            if data.filled {
+                screen.rect(*obstacle, [255, 0, 0, 255]);
+            } else {
+                screen.rect_lines(*obstacle, [255, 0, 0, 255]);
             }
         }
-        window.request_redraw();
-    });
+
+        draw_score(screen, &shared.rsrc, self.score);
+    }
+}
+
+impl Play {
+    // A fresh round, holding something new
+    fn new(rsrc: &Resources) -> Self {
+        let mut sprites = SpriteGraph::new();
+        let mut pigeon_sprite = Sprite::new(
+            &rsrc.pigeon.texture,
+            Animation::new(rsrc.pigeon.clip("glide").unwrap()),
+            Vec2::zero(),
+        );
+        // tilt around the middle of the pigeon frame
+        pigeon_sprite.transform.pivot = Vec2::new(10.0, 8.5);
+        let pigeon = sprites.add(
+            pigeon_sprite,
+            Transform2D::from_translation(pigeon_start()),
+            None,
+        );
+        hold_random(rsrc, &mut sprites, pigeon);
+        sprites.update();
+
+        // the box around the pigeon's circle, so the broadphase finds every
+        // pipe the circle could touch
+        let bounds = Circle::new(pigeon_start(), PIGEON_RADIUS).bounds();
+        Self {
+            player: MovingRect::new(bounds.x, bounds.y, bounds.w, bounds.h, Vec2::zero()),
+            sprites,
+            pigeon,
+            pigeon_animator: pigeon_animator(rsrc),
+            background: Background::new(rsrc),
+            obstacles: Vec::new(),
+            obstacle_data: Vec::new(),
+            gates: Vec::new(),
+            score: 0,
+            move_vel: 1.0,
+            time_between: 3000,
+            pipe_timer: 0,
+            generate: generation::Obstacles {
+                obstacles: vec![(80, 120), (160, 130), (70, 230)],
+                frequency_values: vec![1, 1, 1],
+            },
+        }
+    }
 }
 
 fn play_coo(stream_handle: &OutputStreamHandle) {
@@ -475,72 +464,19 @@ fn play_flap(stream_handle: &OutputStreamHandle) {
     let file = File::open("content/birdflap.mp3").unwrap();
     let flap = rodio::Decoder::new(BufReader::new(file))
         .unwrap()
-        .take_duration(Duration::from_secs(9));
+        .take_duration(Duration::from_millis(400));
     let _ = stream_handle.play_raw(flap.convert_samples());
 }
 
 impl Resources {
     fn new() -> Self {
+        let mut pigeon = SpriteSheet::load(Path::new("content/pigeon.json")).unwrap();
+        // the sound goes with the wings coming down
+        let flap = pigeon.clips.get_mut("flap").unwrap();
+        Rc::make_mut(flap).events.push((1, "flap".to_string()));
         Self {
-            animation_data: vec![
-                // bird glide (pigeon.png)
-                Rc::new(AnimationData {
-                    frames: vec![(Rect::new(0.0, 0.0, 20.0, 17.0), 1)],
-                    looping: false,
-                }),
-                // bird flap
-                Rc::new(AnimationData {
-                    frames: vec![
-                        (Rect::new(20.0, 0.0, 20.0, 17.0), 13),
-                        (Rect::new(40.0, 0.0, 20.0, 17.0), 1),
-                    ],
-                    looping: false,
-                }),
-                // building (buildings.png)
-                Rc::new(AnimationData {
-                    frames: vec![(Rect::new(0.0, 56.0, 23.0, 82.0), 1)],
-                    looping: false,
-                }),
-                // building
-                Rc::new(AnimationData {
-                    frames: vec![(Rect::new(23.0, 21.0, 22.0, 117.0), 1)],
-                    looping: false,
-                }),
-                // building
-                Rc::new(AnimationData {
-                    frames: vec![(Rect::new(45.0, 79.0, 63.0, 58.0), 1)],
-                    looping: false,
-                }),
-                // building
-                Rc::new(AnimationData {
-                    frames: vec![(Rect::new(108.0, 0.0, 29.0, 138.0), 1)],
-                    looping: false,
-                }),
-                // cloud
-                Rc::new(AnimationData {
-                    frames: vec![(Rect::new(45.0, 0.0, 42.0, 29.0), 1)],
-                    looping: false,
-                }),
-                // flower (pigeon.png)
-                Rc::new(AnimationData {
-                    frames: vec![(Rect::new(0.0, 17.0, 8.0, 7.0), 1)],
-                    looping: false,
-                }),
-                // worm
-                Rc::new(AnimationData {
-                    frames: vec![(Rect::new(8.0, 17.0, 6.0, 5.0), 1)],
-                    looping: false,
-                }),
-                // letter
-                Rc::new(AnimationData {
-                    frames: vec![(Rect::new(14.0, 17.0, 9.0, 8.0), 1)],
-                    looping: false,
-                }),
-            ],
-            textures: vec![
-                Rc::new(Texture::with_file(Path::new("content/pigeon.png")).unwrap()),
-                Rc::new(Texture::with_file(Path::new("content/buildings.png")).unwrap()),
-            ],
+            pigeon,
+            buildings: SpriteSheet::load(Path::new("content/buildings.json")).unwrap(),
             text_info: {
                 let image =
                     Rc::new(Texture::with_file(Path::new("content/ascii-dark.png")).unwrap());
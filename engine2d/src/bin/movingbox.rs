@@ -0,0 +1,108 @@
+// Authors: Danny Lee & Jorge Aparicio
+use engine2d::{
+    collision::{self, Overlap},
+    game::{self, Config, Game},
+    input::Input,
+    objects::{Color, MovingRect, Rect, Vec2},
+    screen::Screen,
+};
+use winit::event::VirtualKeyCode;
+const WIDTH: usize = 320;
+const HEIGHT: usize = 240;
+const CLEAR_COL: Color = [32, 32, 64, 255];
+const WALL_COL: Color = [200, 200, 200, 255];
+const PLAYER_COL: Color = [255, 128, 128, 255];
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum ActionID {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+struct GameState {
+    walls: [Rect; 5],
+    mobiles: [MovingRect; 1],
+    contacts: Vec<Overlap>,
+    touches: Vec<Overlap>,
+}
+
+impl Game for GameState {
+    type ActionID = ActionID;
+
+    fn update(&mut self, input: &Input<ActionID>) {
+        let player = &mut self.mobiles[0];
+        // Player control goes here; determine player acceleration
+        if input.is_held(ActionID::Up) {
+            player.y -= 1.0;
+        }
+        if input.is_held(ActionID::Down) {
+            player.y += 1.0;
+        }
+        if input.is_held(ActionID::Left) {
+            player.x -= 1.0;
+        }
+        if input.is_held(ActionID::Right) {
+            player.x += 1.0;
+        }
+        // Determine player velocity
+        // Update player position
+        // Detect collisions: Generate contacts
+        self.contacts.clear();
+        self.touches.clear();
+        collision::gather_overlaps(&self.walls, &self.mobiles, &mut self.contacts);
+        // Handle collisions: Apply restitution impulses.
+        collision::restitute(
+            &self.walls,
+            &mut self.mobiles,
+            &mut self.contacts,
+            &mut self.touches,
+        );
+        // Update game rules: What happens when the player touches things?
+    }
+
+    fn draw(&self, screen: &mut Screen, _alpha: f32) {
+        screen.clear(CLEAR_COL);
+        // Draw the walls
+        for w in self.walls.iter() {
+            screen.rect(*w, WALL_COL);
+        }
+        // Draw the player
+        screen.rect(self.mobiles[0].as_rect(), PLAYER_COL);
+    }
+}
+
+fn main() {
+    let mut input = Input::new();
+    input.add_key_to_map(ActionID::Up, VirtualKeyCode::Up);
+    input.add_key_to_map(ActionID::Down, VirtualKeyCode::Down);
+    input.add_key_to_map(ActionID::Left, VirtualKeyCode::Left);
+    input.add_key_to_map(ActionID::Right, VirtualKeyCode::Right);
+    let player = MovingRect::new(
+        32.0,
+        HEIGHT as f32 - 16.0 - 8.0,
+        8.0,
+        8.0,
+        Vec2::new(0.0, 0.0),
+    );
+    let walls = [
+        Rect::new(0.0, 0.0, WIDTH as f32, 16.0),
+        Rect::new(0.0, 0.0, 16.0, HEIGHT as f32),
+        Rect::new(WIDTH as f32 - 16.0, 0.0, 16.0, HEIGHT as f32),
+        Rect::new(0.0, HEIGHT as f32 - 16.0, WIDTH as f32, 16.0),
+        Rect::new(
+            WIDTH as f32 / 2.0 - 16.0,
+            HEIGHT as f32 / 2.0 - 16.0,
+            32.0,
+            32.0,
+        ),
+    ];
+    let state = GameState {
+        walls,
+        mobiles: [player],
+        contacts: vec![],
+        touches: vec![],
+    };
+    game::run(Config::new("Collision2D", WIDTH, HEIGHT), input, state);
+}
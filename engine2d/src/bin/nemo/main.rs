@@ -1,32 +1,30 @@
+use std::cell::Cell;
 use std::collections::HashMap;
 use std::rc::Rc;
 
 
-use pixels::{Pixels, SurfaceTexture};
 #[allow(unused)]
 use rodio::Source;
 use std::fs::File;
 use std::io::BufReader;
-use winit::dpi::LogicalSize;
-use winit::event::{Event, VirtualKeyCode};
-use winit::event_loop::{ControlFlow, EventLoop};
-use winit::window::WindowBuilder;
-use winit_input_helper::WinitInputHelper; //, PlayError};
+use winit::event::VirtualKeyCode;
 
 use engine2d::{
-    animation::{Animation, AnimationData},
+    animation::{Animation, AnimationData, Playback},
+    atlas::AtlasBuilder,
+    game::{self, Config},
+    input::Input,
     objects::*,
+    scene::{self, Fade, SceneStack},
     screen::Screen,
     sprite::{DrawSpriteExt, Sprite},
     text::*,
-    texture::Texture,
 };
 
 mod storyparser;
 
 const WIDTH: usize = 1280;
 const HEIGHT: usize = 720;
-const DEPTH: usize = 4;
 pub const CHAR_SIZE: f32 = 16.0;
 const BOX_COLOR: Color = [255, 255, 255, 255];
 const BOX_X: f32 = WIDTH as f32 / 10.0;
@@ -34,99 +32,141 @@ const BOX_Y: f32 = 6.0 * HEIGHT as f32 / 11.0;
 const BOX_WIDTH: f32 = 8.0 * WIDTH as f32 / 10.0;
 const BOX_HEIGHT: f32 = 4.0 * HEIGHT as f32 / 10.0;
 
-#[derive(Debug)]
-enum Mode {
-    Title,
-    Read,
-    Respond,
-    EndGame,
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum ActionID {
+    Start,
+    Advance,
+    Up,
+    Down,
 }
 
 use storyparser::*;
-struct GameState {
+
+// What every screen reads from; the story itself never changes
+struct Shared {
     scene_map: HashMap<String, Scene>,
+    text_info: TextInfo,
+    sprites: HashMap<String, Sprite>,
+    title: String,
+}
+
+impl Shared {
+    fn story_scene(&self, name: &str) -> Scene {
+        self.scene_map.get(name).unwrap().clone()
+    }
+
+    // Turns the speaker's sprite the way the scene says
+    fn face(&mut self, scene: &Scene) {
+        if let Some(sprite) = self.sprites.get_mut(&scene.name) {
+            sprite.transform.flip_x = scene
+                .facing
+                .is_some_and(|facing| facing != drawn_facing(&scene.name));
+        }
+    }
+}
+
+// Which way each fish looks in its picture
+fn drawn_facing(name: &str) -> Facing {
+    match name {
+        "Bruce" | "Chum" | "Anchor" => Facing::Left,
+        _ => Facing::Right,
+    }
+}
+
+struct Title;
+
+// Paging through a story scene's message
+struct Read {
     current_scene: Scene,
-    box_read: bool,
-    message_index: usize,
+    // how far into the message the box got filled; only drawing finds that out
+    message_index: Cell<usize>,
     box_text_index: usize,
+}
+
+// Picking one of the current story scene's responses
+struct Respond {
+    current_scene: Scene,
     response_index: usize,
-    text_info: TextInfo,
-    mode: Mode,
 }
 
-impl GameState {
-    pub fn reset_read_info(&mut self) {
-        self.message_index = 0;
-        self.box_text_index = 0;
-        self.response_index = 0;
-        self.box_read = false;
-    }
+struct EndGame;
+
+type Transition = scene::Transition<Shared, ActionID>;
 
-    pub fn reset_game(&mut self) {
-        self.reset_read_info();
-        self.current_scene = self.scene_map.get("intro").unwrap().clone();
-        self.mode = Mode::Title;
+const FADE: Fade = Fade {
+    color: [0, 0, 0, 255],
+    ticks: 30,
+};
+
+impl Read {
+    fn new(shared: &mut Shared, name: &str) -> Self {
+        let current_scene = shared.story_scene(name);
+        shared.face(&current_scene);
+        Self {
+            current_scene,
+            message_index: Cell::new(0),
+            box_text_index: 0,
+        }
     }
 }
 
 mod textinfo;
 
 fn main() {
-    let text_box = Rect::new(BOX_X, BOX_Y, BOX_WIDTH, BOX_HEIGHT);
-    let text_box_text = Rect::new(
-        BOX_X + 3.0 * BOX_WIDTH / 64.0,
-        BOX_Y + CHAR_SIZE * 4.0,
-        BOX_WIDTH - 6.0 * BOX_WIDTH / 64.0,
-        BOX_HEIGHT - CHAR_SIZE * 6.0,
-    );
-
     let story = parse_story().unwrap();
     let title = story.story_name.clone();
     let mut scene_map: HashMap<String, Scene> = HashMap::new();
     let mut sprites: HashMap<String, Sprite> = HashMap::new();
     use std::path::Path;
+    // every fish and the font share one texture
+    let mut atlas = AtlasBuilder::new();
+    atlas
+        .add_file("font", Path::new("content/ascii-dark.png"))
+        .unwrap();
+    let mut fish: Vec<String> = vec![];
     story.scenes.iter().for_each(|s| {
         scene_map.insert(s.scene_name.clone(), s.scene.clone());
-        if !s.scene.name.is_empty() {
-            if let Ok(texture) = Texture::with_file(Path::new(&format!(
-                "content/fishsprites/{}.png",
-                s.scene.name.to_lowercase()
-            ))) {
-                let width = texture.width as f32;
-                let height = texture.height as f32;
-                let animation = Animation::new(&Rc::new(AnimationData {
-                    frames: vec![(Rect::new(0.0, 0.0, width, height), 1)],
-                    looping: false,
-                }));
-                sprites.insert(
-                    s.scene.name.clone(),
-                    Sprite::new(
-                        &Rc::new(texture),
-                        animation,
-                        Vec2::new((WIDTH as f32 - width) / 2.0, 200.0 - height),
-                    ),
-                );
+        let name = &s.scene.name;
+        if !name.is_empty() && !fish.contains(name) {
+            let path = format!("content/fishsprites/{}.png", name.to_lowercase());
+            if atlas.add_file(name, Path::new(&path)).is_ok() {
+                fish.push(name.clone());
             }
         }
     });
+    let atlas = atlas.build().unwrap();
+    for name in fish {
+        let frame = atlas.rect(&name).unwrap();
+        let animation = Animation::new(&Rc::new(AnimationData::new(
+            vec![(frame, 1.0)],
+            Playback::Once,
+        )));
+        sprites.insert(
+            name,
+            Sprite::new(
+                &atlas.texture,
+                animation,
+                Vec2::new((WIDTH as f32 - frame.w) / 2.0, 200.0 - frame.h),
+            ),
+        );
+    }
 
-    let current_scene = scene_map.get("intro").unwrap();
-    let mut state = GameState {
+    let shared = Shared {
         // add tree struct that will represent game text and options. empty until text parser implemented
-        scene_map: scene_map.clone(),
-        current_scene: current_scene.clone(),
-        box_read: false,
-        message_index: 0,
-        box_text_index: 0,
-        response_index: 0,
+        scene_map,
         // position in tree
         //ending_score: 0,
         // ending determiner
         text_info: {
-            let image = Rc::new(Texture::with_file(Path::new("content/ascii-dark.png")).unwrap());
-            TextInfo::new(&image, &textinfo::info())
+            let info = textinfo::info();
+            let info: Vec<(char, Rect)> = info
+                .iter()
+                .map(|&(c, glyph)| (c, atlas.sub_rect("font", glyph).unwrap()))
+                .collect();
+            TextInfo::new(&atlas.texture, &info)
         },
-        mode: Mode::Title,
+        sprites,
+        title: title.clone(),
     };
 
     let (_stream, stream_handle) = rodio::OutputStream::try_default().unwrap();
@@ -138,297 +178,196 @@ fn main() {
 
     let _ = stream_handle.play_raw(background.convert_samples());
 
-    let event_loop = EventLoop::new();
-    let mut input_events = WinitInputHelper::new();
-
-    let window = {
-        let size = LogicalSize::new(WIDTH as f64, HEIGHT as f64);
-        WindowBuilder::new()
-            .with_title(&title)
-            .with_inner_size(size)
-            .with_min_inner_size(size)
-            .with_resizable(false)
-            .build(&event_loop)
-            .unwrap()
-    };
-    let mut pixels = {
-        let window_size = window.inner_size();
-        let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
-        Pixels::new(WIDTH as u32, HEIGHT as u32, surface_texture).unwrap()
-    };
+    let mut input = Input::new();
+    input.add_key_to_map(ActionID::Start, VirtualKeyCode::Return);
+    input.add_key_to_map(ActionID::Advance, VirtualKeyCode::Space);
+    input.add_key_to_map(ActionID::Up, VirtualKeyCode::Up);
+    input.add_key_to_map(ActionID::Down, VirtualKeyCode::Down);
+
+    game::run(
+        Config::new(&title, WIDTH, HEIGHT),
+        input,
+        SceneStack::new(shared, Title),
+    );
+}
+
+fn draw_text_box(screen: &mut Screen) {
+    let text_box = Rect::new(BOX_X, BOX_Y, BOX_WIDTH, BOX_HEIGHT);
+    screen.clear([0, 105, 148, 255]);
+
+    //render text box
+    screen.rect(text_box, BOX_COLOR);
+    screen.rect_lines(text_box, [0, 0, 0, 255]);
+}
 
-    event_loop.run(move |event, _, control_flow| {
-        match state.mode {
-            Mode::Title => {
-                // Draw the current frame
-                if let Event::RedrawRequested(_) = event {
-                    let mut screen = Screen::wrap(pixels.get_frame(), WIDTH, HEIGHT, DEPTH);
-                    screen.clear([0, 105, 148, 255]);
-
-                    screen.draw_text_at_pos(&title, Vec2::new(450.0, 100.0), &state.text_info);
-                    screen.draw_text_at_pos(
-                        "press enter to start.",
-                        Vec2::new(460.0, 440.0),
-                        &state.text_info,
-                    );
-                    screen.draw_text_at_pos("", Vec2::new(65.0, 260.0), &state.text_info);
-
-                    if pixels.render().is_err() {
-                        *control_flow = ControlFlow::Exit;
-                        return;
-                    }
-
-                    //available_time += since.elapsed().as_secs_f64();
-                }
-                // Handle input_events events
-                if input_events.update(&event) {
-                    // Close events
-                    if input_events.key_pressed(VirtualKeyCode::Escape) || input_events.quit() {
-                        *control_flow = ControlFlow::Exit;
-                        return;
-                    }
-                    if input_events.key_pressed(VirtualKeyCode::Return) {
-                        state.mode = Mode::Read;
-                        window.request_redraw();
-                    }
-
-                    // Resize the window
-                    if let Some(size) = input_events.window_resized() {
-                        pixels.resize(size.width, size.height);
-                    }
-                }
+impl scene::Scene<Shared, ActionID> for Title {
+    fn update(&mut self, shared: &mut Shared, input: &Input<ActionID>) -> Transition {
+        if input.is_pressed(ActionID::Start) {
+            Transition::replace(Read::new(shared, "intro")).with_fade(FADE)
+        } else {
+            Transition::None
+        }
+    }
+
+    fn draw(&self, shared: &Shared, screen: &mut Screen, _alpha: f32) {
+        screen.clear([0, 105, 148, 255]);
+
+        screen.draw_text_at_pos(&shared.title, Vec2::new(450.0, 100.0), &shared.text_info);
+        screen.draw_text_at_pos(
+            "press enter to start.",
+            Vec2::new(460.0, 440.0),
+            &shared.text_info,
+        );
+        screen.draw_text_at_pos("", Vec2::new(65.0, 260.0), &shared.text_info);
+    }
+}
+
+impl scene::Scene<Shared, ActionID> for Read {
+    fn update(&mut self, shared: &mut Shared, input: &Input<ActionID>) -> Transition {
+        if !input.is_pressed(ActionID::Advance) {
+            return Transition::None;
+        }
+        self.box_text_index = self.message_index.get();
+        if !self.current_scene.responses.is_empty()
+            && !self.current_scene.responses[0].response.is_empty()
+        {
+            // if player has read all text and has option to give response switch to response mode
+            if self.message_index.get() >= self.current_scene.message.len() - 1 {
+                return Transition::replace(Respond {
+                    current_scene: self.current_scene.clone(),
+                    response_index: 0,
+                });
             }
-            Mode::Read => {
-                // Draw the current frame
-                if let Event::RedrawRequested(_) = event {
-                    let mut screen = Screen::wrap(pixels.get_frame(), WIDTH, HEIGHT, DEPTH);
-                    screen.clear([0, 105, 148, 255]);
-
-                    //render text box
-                    screen.rect(text_box, BOX_COLOR);
-                    screen.rect_lines(text_box, [0, 0, 0, 0]);
-
-                    // draw sprite
-                    if let Some(sprite) = sprites.get(&state.current_scene.name) {
-                        screen.draw_sprite(sprite);
-                    }
-
-                    // render text in box
-                    if !state.current_scene.name.is_empty() {
-                        screen.draw_text_at_pos(
-                            &state.current_scene.name,
-                            Vec2::new(text_box_text.x, BOX_Y + CHAR_SIZE * 2.0),
-                            &state.text_info,
-                        );
-                    }
-                    if let Some(idx) = screen.draw_text_in_rect(
-                        &state.current_scene.message[state.box_text_index..],
-                        text_box_text,
-                        &state.text_info,
-                        false,
-                    ) {
-                        state.message_index = idx;
-                    } else {
-                        state.message_index = state.current_scene.message.len();
-                    }
-
-                    if pixels.render().is_err() {
-                        *control_flow = ControlFlow::Exit;
-                        return;
-                    }
-                }
-
-                // Handle input_events events
-                if input_events.update(&event) {
-                    // Close events
-
-                    if input_events.key_pressed(VirtualKeyCode::Escape) || input_events.quit() {
-                        *control_flow = ControlFlow::Exit;
-                        return;
-                    }
-
-                    if input_events.key_pressed(VirtualKeyCode::Space) || input_events.quit() {
-                        state.box_text_index = state.message_index;
-                        if !state.current_scene.responses.is_empty()
-                            && !state.current_scene.responses[0].response.is_empty()
-                        {
-                            // if player has read all text and has option to give response switch to response mode
-                            if state.message_index >= state.current_scene.message.len() - 1 {
-                                state.mode = Mode::Respond;
-                                state.box_read = false;
-                                state.box_text_index = 0;
-                                state.message_index = 0;
-                            }
-                        } else {
-                            // if player reached end of tree and no final response available switch to game over
-                            if state.current_scene.responses.is_empty() {
-                                state.mode = Mode::EndGame;
-                            } else {
-                                // if no response option available go forward in story
-                                state.current_scene = state
-                                    .scene_map
-                                    .get(&*(state.current_scene.responses[0].goto))
-                                    .unwrap()
-                                    .clone();
-                                state.reset_read_info();
-                            }
-                        }
-
-                        window.request_redraw();
-                    }
-
-                    // Resize the window
-                    if let Some(size) = input_events.window_resized() {
-                        pixels.resize(size.width, size.height);
-                    }
-                }
+        } else if self.current_scene.responses.is_empty() {
+            // if player reached end of tree and no final response available switch to game over
+            return Transition::replace(EndGame);
+        } else {
+            // if no response option available go forward in story
+            *self = Read::new(shared, &self.current_scene.responses[0].goto);
+        }
+        Transition::None
+    }
+
+    fn draw(&self, shared: &Shared, screen: &mut Screen, _alpha: f32) {
+        let text_box_text = Rect::new(
+            BOX_X + 3.0 * BOX_WIDTH / 64.0,
+            BOX_Y + CHAR_SIZE * 4.0,
+            BOX_WIDTH - 6.0 * BOX_WIDTH / 64.0,
+            BOX_HEIGHT - CHAR_SIZE * 6.0,
+        );
+        draw_text_box(screen);
+
+        // draw sprite
+        if let Some(sprite) = shared.sprites.get(&self.current_scene.name) {
+            screen.draw_sprite(sprite);
+        }
+
+        // render text in box
+        if !self.current_scene.name.is_empty() {
+            screen.draw_text_at_pos(
+                &self.current_scene.name,
+                Vec2::new(text_box_text.x, BOX_Y + CHAR_SIZE * 2.0),
+                &shared.text_info,
+            );
+        }
+        if let Some(idx) = screen.draw_text_in_rect(
+            &self.current_scene.message[self.box_text_index..],
+            text_box_text,
+            &shared.text_info,
+            false,
+        ) {
+            self.message_index.set(idx);
+        } else {
+            self.message_index.set(self.current_scene.message.len());
+        }
+    }
+}
+
+impl scene::Scene<Shared, ActionID> for Respond {
+    fn update(&mut self, shared: &mut Shared, input: &Input<ActionID>) -> Transition {
+        //TODO update position in tree
+        if input.is_pressed(ActionID::Down) {
+            if self.response_index < self.current_scene.responses.len() - 1 {
+                self.response_index += 1;
+            } else {
+                self.response_index = 0;
             }
+        }
 
-            Mode::Respond => {
-                // Draw the current frame
-
-                if let Event::RedrawRequested(_) = event {
-                    let mut screen = Screen::wrap(pixels.get_frame(), WIDTH, HEIGHT, DEPTH);
-                    // render background
-                    screen.clear([0, 105, 148, 255]);
-
-                    //render text box
-                    screen.rect(text_box, BOX_COLOR);
-                    screen.rect_lines(text_box, [0, 0, 0, 255]);
-
-                    // vec of response y values for pointer to know location
-                    let mut ypos_vec: Vec<f32> = vec![BOX_Y + CHAR_SIZE];
-
-                    // render responses
-                    for (i, resp_map) in state.current_scene.responses.iter().enumerate() {
-                        let cur_rect = Rect::new(
-                            BOX_X + 3.0 * BOX_WIDTH / 64.0,
-                            ypos_vec[i],
-                            BOX_WIDTH - 6.0 * BOX_WIDTH / 64.0,
-                            BOX_HEIGHT,
-                        );
-
-                        screen.draw_text_in_rect(
-                            &resp_map.response,
-                            cur_rect,
-                            &state.text_info,
-                            false,
-                        );
-                        ypos_vec.push(cur_rect.y + CHAR_SIZE * 2.0);
-                    }
-
-                    // response pointer
-                    let pointer = Rect {
-                        x: BOX_X + 1.0 * BOX_WIDTH / 64.0,
-                        y: ypos_vec[state.response_index],
-                        h: 8.0,
-                        w: 8.0,
-                    };
-                    screen.rect(pointer, [255, 0, 0, 255]);
-
-                    if pixels.render().is_err() {
-                        *control_flow = ControlFlow::Exit;
-                        return;
-                    }
-                }
-
-                //TODO update position in tree
-
-                let mut input = false;
-
-                // Handle input_events events
-                if input_events.update(&event) {
-                    // Close events
-                    if input_events.key_pressed(VirtualKeyCode::Escape) || input_events.quit() {
-                        *control_flow = ControlFlow::Exit;
-                        return;
-                    }
-
-                    if input_events.key_pressed(VirtualKeyCode::Down) {
-                        if state.response_index < state.current_scene.responses.len() - 1 {
-                            state.response_index += 1;
-                        } else {
-                            state.response_index = 0;
-                        }
-                        input = true;
-                    }
-
-                    if input_events.key_pressed(VirtualKeyCode::Up) {
-                        if state.response_index > 0 {
-                            state.response_index -= 1;
-                        } else {
-                            state.response_index = state.current_scene.responses.len() - 1;
-                        }
-                        input = true;
-                    }
-
-                    if input_events.key_pressed(VirtualKeyCode::Space) {
-                        //move to next value in tree based on response.
-                        if state.current_scene.responses.is_empty() {
-                            state.mode = Mode::EndGame;
-                        } else {
-                            state.current_scene = state
-                                .scene_map
-                                .get(&*(state.current_scene.responses[state.response_index].goto))
-                                .unwrap()
-                                .clone();
-                            state.reset_read_info();
-                            state.mode = Mode::Read;
-                        }
-                        input = true;
-                    }
-
-                    if input {
-                        window.request_redraw();
-                    }
-
-                    // Resize the window
-                    if let Some(size) = input_events.window_resized() {
-                        pixels.resize(size.width, size.height);
-                    }
-                }
+        if input.is_pressed(ActionID::Up) {
+            if self.response_index > 0 {
+                self.response_index -= 1;
+            } else {
+                self.response_index = self.current_scene.responses.len() - 1;
             }
+        }
 
-            Mode::EndGame => {
-                if let Event::RedrawRequested(_) = event {
-                    let mut screen = Screen::wrap(pixels.get_frame(), WIDTH, HEIGHT, DEPTH);
-                    screen.clear([100, 150, 200, 255]);
-
-                    screen.draw_text_at_pos("the end", Vec2::new(400.0, 60.0), &state.text_info);
-
-                    screen.draw_text_at_pos(
-                        "press enter to return to title screen",
-                        Vec2::new(400.0, 240.0),
-                        &state.text_info,
-                    );
-                    screen.draw_text_at_pos(
-                        "or escape to exit",
-                        Vec2::new(300.0, 260.0),
-                        &state.text_info,
-                    );
-
-                    if pixels.render().is_err() {
-                        *control_flow = ControlFlow::Exit;
-                        return;
-                    }
-                }
-                if input_events.update(&event) {
-                    // Close events
-                    if input_events.key_pressed(VirtualKeyCode::Escape) || input_events.quit() {
-                        *control_flow = ControlFlow::Exit;
-                        return;
-                    }
-                    if input_events.key_pressed(VirtualKeyCode::Return) {
-                        // reset game mode to title, state values to default
-                        state.reset_game();
-                        window.request_redraw();
-                    }
-                    // Resize the window
-                    if let Some(size) = input_events.window_resized() {
-                        pixels.resize(size.width, size.height);
-                    }
-                }
+        if input.is_pressed(ActionID::Advance) {
+            //move to next value in tree based on response.
+            if self.current_scene.responses.is_empty() {
+                return Transition::replace(EndGame);
             }
+            let goto = &self.current_scene.responses[self.response_index].goto;
+            return Transition::replace(Read::new(shared, goto));
         }
-    });
+        Transition::None
+    }
+
+    fn draw(&self, shared: &Shared, screen: &mut Screen, _alpha: f32) {
+        // render background
+        draw_text_box(screen);
+
+        // vec of response y values for pointer to know location
+        let mut ypos_vec: Vec<f32> = vec![BOX_Y + CHAR_SIZE];
+
+        // render responses
+        for (i, resp_map) in self.current_scene.responses.iter().enumerate() {
+            let cur_rect = Rect::new(
+                BOX_X + 3.0 * BOX_WIDTH / 64.0,
+                ypos_vec[i],
+                BOX_WIDTH - 6.0 * BOX_WIDTH / 64.0,
+                BOX_HEIGHT,
+            );
+
+            screen.draw_text_in_rect(&resp_map.response, cur_rect, &shared.text_info, false);
+            ypos_vec.push(cur_rect.y + CHAR_SIZE * 2.0);
+        }
+
+        // response pointer
+        let pointer = Rect {
+            x: BOX_X + 1.0 * BOX_WIDTH / 64.0,
+            y: ypos_vec[self.response_index],
+            h: 8.0,
+            w: 8.0,
+        };
+        screen.rect(pointer, [255, 0, 0, 255]);
+    }
+}
+
+impl scene::Scene<Shared, ActionID> for EndGame {
+    fn update(&mut self, _shared: &mut Shared, input: &Input<ActionID>) -> Transition {
+        if input.is_pressed(ActionID::Start) {
+            // back to the title; the next run starts from the intro again
+            Transition::replace(Title).with_fade(FADE)
+        } else {
+            Transition::None
+        }
+    }
+
+    fn draw(&self, shared: &Shared, screen: &mut Screen, _alpha: f32) {
+        screen.clear([100, 150, 200, 255]);
+
+        screen.draw_text_at_pos("the end", Vec2::new(400.0, 60.0), &shared.text_info);
+
+        screen.draw_text_at_pos(
+            "press enter to return to title screen",
+            Vec2::new(400.0, 240.0),
+            &shared.text_info,
+        );
+        screen.draw_text_at_pos(
+            "or escape to exit",
+            Vec2::new(300.0, 260.0),
+            &shared.text_info,
+        );
+    }
 }
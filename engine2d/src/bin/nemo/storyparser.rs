@@ -18,6 +18,16 @@ pub struct Scene {
     pub name: String,
     pub message: String,
     pub responses: Vec<Response>,
+    // Which way the speaker looks; left out, their picture is drawn as is
+    #[serde(default)]
+    pub facing: Option<Facing>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Facing {
+    Left,
+    Right,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
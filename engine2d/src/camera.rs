@@ -0,0 +1,71 @@
+use crate::objects::{Rect, Vec2};
+
+// Where the screen is looking in the world. Screen runs every draw call
+// through its camera, so scrolling means moving this instead of every object.
+#[derive(Copy, Clone, PartialEq)]
+pub struct Camera {
+    // World position that shows up at the top-left corner of the screen
+    pub offset: Vec2,
+    // Screen pixels per world unit
+    pub zoom: f32,
+    // Screen-space area drawing is limited to, None for the whole screen
+    pub clip: Option<Rect>,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self::new(Vec2::new(0.0, 0.0), 1.0)
+    }
+}
+
+impl Camera {
+    pub fn new(offset: Vec2, zoom: f32) -> Self {
+        Self {
+            offset,
+            zoom,
+            clip: None,
+        }
+    }
+
+    // Identity cameras draw in raw screen pixels, e.g. for HUDs
+    pub fn is_identity(&self) -> bool {
+        self.offset.x == 0.0 && self.offset.y == 0.0 && self.zoom == 1.0 && self.clip.is_none()
+    }
+
+    pub fn world_to_screen(&self, p: Vec2) -> Vec2 {
+        Vec2::new(
+            (p.x - self.offset.x) * self.zoom,
+            (p.y - self.offset.y) * self.zoom,
+        )
+    }
+
+    pub fn screen_to_world(&self, p: Vec2) -> Vec2 {
+        Vec2::new(
+            p.x / self.zoom + self.offset.x,
+            p.y / self.zoom + self.offset.y,
+        )
+    }
+
+    pub fn world_rect_to_screen(&self, r: Rect) -> Rect {
+        let p = self.world_to_screen(r.pos());
+        Rect::new(p.x, p.y, r.w * self.zoom, r.h * self.zoom)
+    }
+
+    // The part of the world a screen of this size shows
+    pub fn view(&self, (width, height): (usize, usize)) -> Rect {
+        Rect::new(
+            self.offset.x,
+            self.offset.y,
+            width as f32 / self.zoom,
+            height as f32 / self.zoom,
+        )
+    }
+
+    // Put `p` in the middle of a screen of this size
+    pub fn center_on(&mut self, p: Vec2, (width, height): (usize, usize)) {
+        self.offset = Vec2::new(
+            p.x - width as f32 / (2.0 * self.zoom),
+            p.y - height as f32 / (2.0 * self.zoom),
+        );
+    }
+}
@@ -0,0 +1,55 @@
+use crate::objects::Rect;
+use crate::screen::Screen;
+use crate::texture::Texture;
+use std::rc::Rc;
+
+const DEPTH: usize = 4;
+
+// An offscreen framebuffer. Draw into it through `screen()` with anything
+// that works on the real window, then keep the result as a texture.
+pub struct Canvas {
+    buffer: Vec<u8>,
+    width: usize,
+    height: usize,
+}
+
+impl Canvas {
+    // Starts out fully transparent
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            buffer: vec![0; width * height * DEPTH],
+            width,
+            height,
+        }
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    // The whole canvas, handy as the `from` rect when blitting the texture
+    pub fn frame(&self) -> Rect {
+        Rect::new(0.0, 0.0, self.width as f32, self.height as f32)
+    }
+
+    pub fn screen(&mut self) -> Screen<'_> {
+        Screen::wrap(&mut self.buffer, self.width, self.height, DEPTH)
+    }
+
+    // Copies the current contents, so the canvas can keep being drawn into
+    pub fn to_texture(&self) -> Rc<Texture> {
+        Rc::new(Texture::from_premultiplied(
+            self.buffer.clone(),
+            self.width,
+            self.height,
+        ))
+    }
+
+    pub fn into_texture(self) -> Rc<Texture> {
+        Rc::new(Texture::from_premultiplied(
+            self.buffer,
+            self.width,
+            self.height,
+        ))
+    }
+}
@@ -1,29 +1,1485 @@
-use crate::objects::{MovingRect, Rect};
+use crate::objects::{MovingRect, Rect, Vec2};
+use std::collections::{BTreeSet, HashMap};
 
-pub struct Contact(ContactID, ContactID);
+// Which pairs of colliders get to interact. A collider is on some layers
+// (bits of `layer`) and looks for others on the layers in `mask`; two
+// colliders interact only if each one's mask has a layer of the other's.
+// Triggers still report contacts, but never get pushed out of anything.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Layers {
+    pub layer: u32,
+    pub mask: u32,
+    pub trigger: bool,
+}
+
+impl Layers {
+    pub fn new(layer: u32, mask: u32) -> Self {
+        Self {
+            layer,
+            mask,
+            trigger: false,
+        }
+    }
+
+    pub fn trigger(layer: u32, mask: u32) -> Self {
+        Self {
+            layer,
+            mask,
+            trigger: true,
+        }
+    }
+
+    pub fn interacts(&self, other: &Layers) -> bool {
+        self.mask & other.layer != 0 && other.mask & self.layer != 0
+    }
+}
 
-impl Contact {
-    pub fn get_ids(&self) -> (ContactID, ContactID) {
-        (self.0, self.1)
+impl Default for Layers {
+    // On the first layer, hitting everything
+    fn default() -> Self {
+        Self::new(1, u32::MAX)
     }
 }
 
-#[derive(Copy, Clone)]
-pub enum ContactID {
-    Obstacle,
-    Player,
+// For collider ID types that know which layers they're on
+pub trait HasLayers {
+    fn layers(&self) -> Layers;
+}
+
+// A mover touching an obstacle. `ID` is whatever the game wants to tag its
+// colliders with (an enum of kinds, an entity id...), and the indices say
+// where each one was in the slices given to gather_contacts.
+// `trigger` is set if either side was a trigger.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Contact<ID> {
+    pub a: ID,
+    pub b: ID,
+    pub a_index: usize,
+    pub b_index: usize,
+    pub trigger: bool,
+}
+
+impl<ID: Copy> Contact<ID> {
+    pub fn get_ids(&self) -> (ID, ID) {
+        (self.a, self.b)
+    }
+
+    pub fn get_indices(&self) -> (usize, usize) {
+        (self.a_index, self.b_index)
+    }
 }
 
-pub fn gather_contacts(player: &MovingRect, obstacles: &[Rect]) -> Vec<Contact> {
+// `layers` decides whether a pair of IDs counts at all, and if so whether
+// it's a trigger contact
+fn contacts_between<ID: Copy>(
+    movers: &[MovingRect],
+    mover_ids: &[ID],
+    obstacles: &[Rect],
+    obstacle_ids: &[ID],
+    layers: impl Fn(&ID, &ID) -> Option<bool>,
+) -> Vec<Contact<ID>> {
+    assert_eq!(movers.len(), mover_ids.len());
+    assert_eq!(obstacles.len(), obstacle_ids.len());
     let mut contacts = Vec::new();
-    for obstacle in obstacles.iter() {
-        if player.x <= obstacle.x + obstacle.w
-            && obstacle.x <= player.x + player.w
-            && player.y <= obstacle.y + obstacle.h
-            && obstacle.y <= player.y + player.h
-        {
-            contacts.push(Contact(ContactID::Player, ContactID::Obstacle));
+    for (a_index, mover) in movers.iter().enumerate() {
+        for (b_index, obstacle) in obstacles.iter().enumerate() {
+            let (a, b) = (mover_ids[a_index], obstacle_ids[b_index]);
+            let trigger = match layers(&a, &b) {
+                Some(trigger) => trigger,
+                None => continue,
+            };
+            if rect_touching(mover.as_rect(), *obstacle) {
+                contacts.push(Contact {
+                    a,
+                    b,
+                    a_index,
+                    b_index,
+                    trigger,
+                });
+            }
         }
     }
     contacts
 }
+
+// `mover_ids` and `obstacle_ids` line up with `movers` and `obstacles`
+pub fn gather_contacts<ID: Copy>(
+    movers: &[MovingRect],
+    mover_ids: &[ID],
+    obstacles: &[Rect],
+    obstacle_ids: &[ID],
+) -> Vec<Contact<ID>> {
+    contacts_between(movers, mover_ids, obstacles, obstacle_ids, |_, _| {
+        Some(false)
+    })
+}
+
+// Like gather_contacts, but skips pairs whose layers don't interact
+pub fn gather_layered_contacts<ID: Copy + HasLayers>(
+    movers: &[MovingRect],
+    mover_ids: &[ID],
+    obstacles: &[Rect],
+    obstacle_ids: &[ID],
+) -> Vec<Contact<ID>> {
+    contacts_between(movers, mover_ids, obstacles, obstacle_ids, |a, b| {
+        let (a, b) = (a.layers(), b.layers());
+        if a.interacts(&b) {
+            Some(a.trigger || b.trigger)
+        } else {
+            None
+        }
+    })
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ContactEvent<ID> {
+    // started touching this frame
+    Began(ID, ID),
+    // touching this frame and last frame too
+    Persisted(ID, ID),
+    // touched last frame but not this one
+    Ended(ID, ID),
+}
+
+// Remembers which pairs were touching last frame, so games can react to
+// contacts starting and stopping instead of working it out from scratch.
+// Pairs are matched by ID, not index, so IDs need to stay put across frames.
+pub struct ContactTracker<ID: Ord + Copy> {
+    this_frame_pairs: BTreeSet<(ID, ID)>,
+    last_frame_pairs: BTreeSet<(ID, ID)>,
+}
+
+impl<ID: Ord + Copy> Default for ContactTracker<ID> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<ID: Ord + Copy> ContactTracker<ID> {
+    pub fn new() -> Self {
+        Self {
+            this_frame_pairs: BTreeSet::new(),
+            last_frame_pairs: BTreeSet::new(),
+        }
+    }
+
+    // Call once per step with that step's contacts
+    pub fn update(&mut self, contacts: &[Contact<ID>]) {
+        self.last_frame_pairs = std::mem::take(&mut self.this_frame_pairs);
+        for contact in contacts.iter() {
+            self.this_frame_pairs.insert(contact.get_ids());
+        }
+    }
+
+    // Forgets everything, e.g. when restarting a level
+    pub fn clear(&mut self) {
+        self.this_frame_pairs.clear();
+        self.last_frame_pairs.clear();
+    }
+
+    pub fn is_touching(&self, a: ID, b: ID) -> bool {
+        self.this_frame_pairs.contains(&(a, b))
+    }
+
+    pub fn began(&self, a: ID, b: ID) -> bool {
+        self.this_frame_pairs.contains(&(a, b)) && !self.last_frame_pairs.contains(&(a, b))
+    }
+
+    pub fn persisted(&self, a: ID, b: ID) -> bool {
+        self.this_frame_pairs.contains(&(a, b)) && self.last_frame_pairs.contains(&(a, b))
+    }
+
+    pub fn ended(&self, a: ID, b: ID) -> bool {
+        !self.this_frame_pairs.contains(&(a, b)) && self.last_frame_pairs.contains(&(a, b))
+    }
+
+    // Every pair that began, persisted or ended this frame
+    pub fn events(&self) -> impl Iterator<Item = ContactEvent<ID>> + '_ {
+        let current = self.this_frame_pairs.iter().map(move |&(a, b)| {
+            if self.last_frame_pairs.contains(&(a, b)) {
+                ContactEvent::Persisted(a, b)
+            } else {
+                ContactEvent::Began(a, b)
+            }
+        });
+        let ended = self
+            .last_frame_pairs
+            .difference(&self.this_frame_pairs)
+            .map(|&(a, b)| ContactEvent::Ended(a, b));
+        current.chain(ended)
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ColliderID {
+    Static(usize),
+    Dynamic(usize),
+}
+
+// Two colliders overlapping; `a` is always a Dynamic.
+// `mtv` is the minimum translation vector: moving `a` by it separates the two.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Overlap {
+    pub a: ColliderID,
+    pub b: ColliderID,
+    pub mtv: Vec2,
+}
+
+pub fn rect_touching(r1: Rect, r2: Rect) -> bool {
+    // r1 left is left of r2 right
+    r1.x <= r2.x + r2.w &&
+        // r2 left is left of r1 right
+        r2.x <= r1.x + r1.w &&
+        // those two conditions handle the x axis overlap;
+        // the next two do the same for the y axis:
+        r1.y <= r2.y + r2.h &&
+        r2.y <= r1.y + r1.h
+}
+
+// The signed MTV that pushes r1 out of r2 along whichever axis overlaps less,
+// or None if they don't overlap (just touching edges doesn't count).
+pub fn rect_displacement(r1: Rect, r2: Rect) -> Option<Vec2> {
+    // Draw this out on paper to double check, but these quantities
+    // will both be positive exactly when the rects overlap.
+    let x_overlap = (r1.x + r1.w).min(r2.x + r2.w) - r1.x.max(r2.x);
+    let y_overlap = (r1.y + r1.h).min(r2.y + r2.h) - r1.y.max(r2.y);
+    if x_overlap <= 0.0 || y_overlap <= 0.0 {
+        return None;
+    }
+    // Push away from the other rect's center
+    if x_overlap < y_overlap {
+        let dir = if r1.x + r1.w / 2.0 < r2.x + r2.w / 2.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        Some(Vec2::new(x_overlap * dir, 0.0))
+    } else {
+        let dir = if r1.y + r1.h / 2.0 < r2.y + r2.h / 2.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        Some(Vec2::new(0.0, y_overlap * dir))
+    }
+}
+
+// Here we will be using push() on into, so it can't be a slice
+pub fn gather_overlaps(statics: &[Rect], dynamics: &[MovingRect], into: &mut Vec<Overlap>) {
+    // collide mobiles against mobiles
+    for (ai, a) in dynamics.iter().enumerate() {
+        for (bi, b) in dynamics.iter().enumerate().skip(ai + 1) {
+            if let Some(mtv) = rect_displacement(a.as_rect(), b.as_rect()) {
+                into.push(Overlap {
+                    a: ColliderID::Dynamic(ai),
+                    b: ColliderID::Dynamic(bi),
+                    mtv,
+                });
+            }
+        }
+    }
+    // collide mobiles against walls
+    for (ai, a) in dynamics.iter().enumerate() {
+        for (bi, b) in statics.iter().enumerate() {
+            if let Some(mtv) = rect_displacement(a.as_rect(), *b) {
+                into.push(Overlap {
+                    a: ColliderID::Dynamic(ai),
+                    b: ColliderID::Static(bi),
+                    mtv,
+                });
+            }
+        }
+    }
+}
+
+// Like gather_overlaps, but `static_layers` and `dynamic_layers` (lined up
+// with the colliders) filter out pairs that don't interact, and overlaps
+// involving a trigger go into `triggers` instead, so restitute never sees them.
+pub fn gather_layered_overlaps(
+    statics: &[Rect],
+    static_layers: &[Layers],
+    dynamics: &[MovingRect],
+    dynamic_layers: &[Layers],
+    into: &mut Vec<Overlap>,
+    triggers: &mut Vec<Overlap>,
+) {
+    assert_eq!(statics.len(), static_layers.len());
+    assert_eq!(dynamics.len(), dynamic_layers.len());
+    let mut sort = |a: Layers, b: Layers, overlap: Overlap| {
+        if !a.interacts(&b) {
+            return;
+        }
+        if a.trigger || b.trigger {
+            triggers.push(overlap);
+        } else {
+            into.push(overlap);
+        }
+    };
+    for (ai, a) in dynamics.iter().enumerate() {
+        for (bi, b) in dynamics.iter().enumerate().skip(ai + 1) {
+            if let Some(mtv) = rect_displacement(a.as_rect(), b.as_rect()) {
+                let overlap = Overlap {
+                    a: ColliderID::Dynamic(ai),
+                    b: ColliderID::Dynamic(bi),
+                    mtv,
+                };
+                sort(dynamic_layers[ai], dynamic_layers[bi], overlap);
+            }
+        }
+        for (bi, b) in statics.iter().enumerate() {
+            if let Some(mtv) = rect_displacement(a.as_rect(), *b) {
+                let overlap = Overlap {
+                    a: ColliderID::Dynamic(ai),
+                    b: ColliderID::Static(bi),
+                    mtv,
+                };
+                sort(dynamic_layers[ai], static_layers[bi], overlap);
+            }
+        }
+    }
+}
+
+// Every pair (i, j), i < j, of touching rects, checking all of them against
+// each other. Fine for a handful of rects; see SpatialHash for more.
+pub fn brute_force_pairs(rects: &[Rect], into: &mut Vec<(usize, usize)>) {
+    for (ai, a) in rects.iter().enumerate() {
+        for (bi, b) in rects.iter().enumerate().skip(ai + 1) {
+            if rect_touching(*a, *b) {
+                into.push((ai, bi));
+            }
+        }
+    }
+}
+
+type Cell = (i32, i32);
+
+// A broadphase: rects get bucketed into a uniform grid of square cells, so
+// only rects sharing a cell are ever compared. Cells live in a hash map, so
+// the world can be any size. Meant to be cleared and refilled every frame;
+// a cell size around the size of a typical rect works best.
+pub struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<Cell, Vec<usize>>,
+    // cells with something in them since the last clear; they're the only
+    // ones in `cells`
+    occupied: Vec<Cell>,
+    // emptied buckets, kept to be reused rather than reallocated
+    spare: Vec<Vec<usize>>,
+    rects: Vec<Rect>,
+    // first and last cells (inclusive) each rect covers
+    spans: Vec<(Cell, Cell)>,
+}
+
+impl SpatialHash {
+    pub fn new(cell_size: f32) -> Self {
+        assert!(cell_size > 0.0);
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+            occupied: vec![],
+            spare: vec![],
+            rects: vec![],
+            spans: vec![],
+        }
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    pub fn len(&self) -> usize {
+        self.rects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty()
+    }
+
+    pub fn rect(&self, index: usize) -> Rect {
+        self.rects[index]
+    }
+
+    // Empties the grid but keeps its allocations around for the next frame.
+    // Buckets come out of the map, so it doesn't fill up with empty cells
+    // as things move around the world.
+    pub fn clear(&mut self) {
+        for cell in self.occupied.drain(..) {
+            if let Some(mut bucket) = self.cells.remove(&cell) {
+                bucket.clear();
+                self.spare.push(bucket);
+            }
+        }
+        self.rects.clear();
+        self.spans.clear();
+    }
+
+    // Clears the grid and inserts `rects`, so their indices line up with it
+    pub fn rebuild(&mut self, rects: &[Rect]) {
+        self.clear();
+        for r in rects.iter() {
+            self.insert(*r);
+        }
+    }
+
+    // Returns the index the rect was given (they count up from 0 after a clear)
+    pub fn insert(&mut self, r: Rect) -> usize {
+        let index = self.rects.len();
+        let span = self.span(r);
+        let ((x0, y0), (x1, y1)) = span;
+        let spare = &mut self.spare;
+        for cy in y0..=y1 {
+            for cx in x0..=x1 {
+                let bucket = self
+                    .cells
+                    .entry((cx, cy))
+                    .or_insert_with(|| spare.pop().unwrap_or_default());
+                if bucket.is_empty() {
+                    self.occupied.push((cx, cy));
+                }
+                bucket.push(index);
+            }
+        }
+        self.rects.push(r);
+        self.spans.push(span);
+        index
+    }
+
+    // Inclusive on both ends, so rects that just touch share a cell
+    fn span(&self, r: Rect) -> (Cell, Cell) {
+        let cell = |v: f32| (v / self.cell_size).floor() as i32;
+        ((cell(r.x), cell(r.y)), (cell(r.x + r.w), cell(r.y + r.h)))
+    }
+
+    // Indices of every rect touching `r`, in increasing order
+    pub fn query(&self, r: Rect, into: &mut Vec<usize>) {
+        let start = into.len();
+        let ((x0, y0), (x1, y1)) = self.span(r);
+        for cy in y0..=y1 {
+            for cx in x0..=x1 {
+                if let Some(bucket) = self.cells.get(&(cx, cy)) {
+                    into.extend(
+                        bucket
+                            .iter()
+                            .copied()
+                            .filter(|&i| rect_touching(r, self.rects[i])),
+                    );
+                }
+            }
+        }
+        // a rect spanning several cells shows up once per cell
+        let mut found = into.split_off(start);
+        found.sort_unstable();
+        found.dedup();
+        into.extend(found);
+    }
+
+    // Every pair (i, j), i < j, of touching rects, each reported once; the
+    // same pairs brute_force_pairs would give, though not in the same order.
+    pub fn pairs(&self, into: &mut Vec<(usize, usize)>) {
+        for &cell in self.occupied.iter() {
+            let bucket = &self.cells[&cell];
+            for (n, &ai) in bucket.iter().enumerate() {
+                for &bi in bucket.iter().skip(n + 1) {
+                    let (a0, _) = self.spans[ai];
+                    let (b0, _) = self.spans[bi];
+                    // Two rects can share lots of cells; only the first one
+                    // they share (top-left) gets to report the pair.
+                    if cell != (a0.0.max(b0.0), a0.1.max(b0.1)) {
+                        continue;
+                    }
+                    if rect_touching(self.rects[ai], self.rects[bi]) {
+                        into.push((ai.min(bi), ai.max(bi)));
+                    }
+                }
+            }
+        }
+    }
+
+    // Indices of every rect under the point, in increasing order
+    pub fn query_point(&self, p: Vec2, into: &mut Vec<usize>) {
+        self.query(Rect::new(p.x, p.y, 0.0, 0.0), into);
+    }
+
+    // Same as the raycast function, but only looks at the cells the ray
+    // passes through, nearest first. `max_distance` has to be finite, since
+    // a ray that misses everything keeps walking cells until it gets there.
+    pub fn raycast(&self, origin: Vec2, dir: Vec2, max_distance: f32) -> Option<RayHit> {
+        assert!(max_distance.is_finite());
+        let dir = dir.try_normalize()?;
+        let size = self.cell_size;
+        let mut cell = (
+            (origin.x / size).floor() as i32,
+            (origin.y / size).floor() as i32,
+        );
+        // how far along the ray the next cell boundary on each axis is, and
+        // how far apart boundaries are on that axis
+        let axis = |o: f32, d: f32, c: i32| {
+            if d > 0.0 {
+                (1, ((c + 1) as f32 * size - o) / d, size / d)
+            } else if d < 0.0 {
+                (-1, (c as f32 * size - o) / d, -size / d)
+            } else {
+                (0, f32::INFINITY, f32::INFINITY)
+            }
+        };
+        let (step_x, mut next_x, delta_x) = axis(origin.x, dir.x, cell.0);
+        let (step_y, mut next_y, delta_y) = axis(origin.y, dir.y, cell.1);
+        let mut best = None;
+        loop {
+            if let Some(bucket) = self.cells.get(&cell) {
+                for &index in bucket.iter() {
+                    if let Some(hit) = ray_rect(index, origin, dir, max_distance, self.rects[index])
+                    {
+                        best = closer(best, hit);
+                    }
+                }
+            }
+            let leave = next_x.min(next_y);
+            // nothing in a later cell can be nearer than this cell's exit
+            if best.is_some_and(|b: RayHit| b.distance <= leave) || leave > max_distance {
+                return best;
+            }
+            if next_x < next_y {
+                cell.0 += step_x;
+                next_x += delta_x;
+            } else {
+                cell.1 += step_y;
+                next_y += delta_y;
+            }
+        }
+    }
+
+    pub fn segment_cast(&self, from: Vec2, to: Vec2) -> Option<RayHit> {
+        let d = to - from;
+        self.raycast(from, d, d.length())
+    }
+}
+
+// Stop a mover's velocity from pointing back along the way it was just pushed
+// (velocity here is added to position, so +y is down)
+fn cancel_velocity(mover: &mut MovingRect, push: Vec2) {
+    if push.x * mover.vel.x < 0.0 {
+        mover.vel.x = 0.0;
+    }
+    if push.y * mover.vel.y < 0.0 {
+        mover.vel.y = 0.0;
+    }
+}
+
+// Pushes dynamics out of statics and out of each other, biggest overlaps first.
+// Each overlap is re-measured before resolving it, since an earlier push may
+// have already fixed it; the ones that still needed a push go into `touches`,
+// with the MTV that was actually applied to `a`. Dynamic pairs split the push.
+pub fn restitute(
+    statics: &[Rect],
+    dynamics: &mut [MovingRect],
+    overlaps: &mut [Overlap],
+    touches: &mut Vec<Overlap>,
+) {
+    overlaps.sort_unstable_by(|c1, c2| {
+        let m1 = c1.mtv.x * c1.mtv.x + c1.mtv.y * c1.mtv.y;
+        let m2 = c2.mtv.x * c2.mtv.x + c2.mtv.y * c2.mtv.y;
+        m2.partial_cmp(&m1).unwrap_or(std::cmp::Ordering::Equal)
+    });
+    for overlap in overlaps.iter() {
+        let ai = match overlap.a {
+            ColliderID::Dynamic(ai) => ai,
+            ColliderID::Static(_) => continue,
+        };
+        match overlap.b {
+            ColliderID::Static(bi) => {
+                let a = &mut dynamics[ai];
+                if let Some(mtv) = rect_displacement(a.as_rect(), statics[bi]) {
+                    a.x += mtv.x;
+                    a.y += mtv.y;
+                    cancel_velocity(a, mtv);
+                    touches.push(Overlap { mtv, ..*overlap });
+                }
+            }
+            ColliderID::Dynamic(bi) => {
+                if let Some(mtv) = rect_displacement(dynamics[ai].as_rect(), dynamics[bi].as_rect())
+                {
+                    let half = Vec2::new(mtv.x / 2.0, mtv.y / 2.0);
+                    let a = &mut dynamics[ai];
+                    a.x += half.x;
+                    a.y += half.y;
+                    cancel_velocity(a, half);
+                    let b = &mut dynamics[bi];
+                    b.x -= half.x;
+                    b.y -= half.y;
+                    cancel_velocity(b, Vec2::new(-half.x, -half.y));
+                    touches.push(Overlap {
+                        mtv: half,
+                        ..*overlap
+                    });
+                }
+            }
+        }
+    }
+}
+
+// Where a swept mover first runs into something. `time` is how far along
+// its velocity it got (0.0 is where it started, 1.0 is the full step), and
+// `normal` points out of the thing it hit, along the side it hit.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Hit {
+    pub time: f32,
+    pub normal: Vec2,
+}
+
+// When the span a0..a1 moving by v starts and stops overlapping b0..b1.
+// If it isn't moving it either always or never overlaps.
+fn sweep_axis(a0: f32, a1: f32, v: f32, b0: f32, b1: f32) -> Option<(f32, f32)> {
+    if v == 0.0 {
+        if a0 < b1 && b0 < a1 {
+            Some((f32::NEG_INFINITY, f32::INFINITY))
+        } else {
+            None
+        }
+    } else {
+        let t0 = (b0 - a1) / v;
+        let t1 = (b1 - a0) / v;
+        Some((t0.min(t1), t0.max(t1)))
+    }
+}
+
+// Swept AABB: moves `mover` along its whole `vel` and reports when it first
+// touches `obstacle`, so fast movers can't skip over thin walls.
+// Things that already overlap at the start don't count; that's what
+// restitute is for.
+pub fn sweep_rect(mover: &MovingRect, obstacle: Rect) -> Option<Hit> {
+    let (x_entry, x_exit) = sweep_axis(
+        mover.x,
+        mover.x + mover.w,
+        mover.vel.x,
+        obstacle.x,
+        obstacle.x + obstacle.w,
+    )?;
+    let (y_entry, y_exit) = sweep_axis(
+        mover.y,
+        mover.y + mover.h,
+        mover.vel.y,
+        obstacle.y,
+        obstacle.y + obstacle.h,
+    )?;
+    let entry = x_entry.max(y_entry);
+    let exit = x_exit.min(y_exit);
+    // a little slack at the start so a mover left resting right against
+    // something by rounding still hits it
+    if entry >= exit || !(-EPSILON..=1.0).contains(&entry) {
+        return None;
+    }
+    // whichever axis started overlapping last is the side that got hit
+    let normal = if x_entry > y_entry {
+        Vec2::new(-mover.vel.x.signum(), 0.0)
+    } else {
+        Vec2::new(0.0, -mover.vel.y.signum())
+    };
+    Some(Hit {
+        time: entry.max(0.0),
+        normal,
+    })
+}
+
+// The first of `obstacles` the mover would hit this step, and where
+pub fn sweep_rects(mover: &MovingRect, obstacles: &[Rect]) -> Option<(usize, Hit)> {
+    obstacles
+        .iter()
+        .enumerate()
+        .filter_map(|(i, ob)| sweep_rect(mover, *ob).map(|hit| (i, hit)))
+        .min_by(|(_, h1), (_, h2)| {
+            h1.time
+                .partial_cmp(&h2.time)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+}
+
+// Moves `mover` by its `vel`, stopping at the first obstacle in the way and
+// then sliding along it with whatever motion is left; velocity into the
+// obstacles hit gets cancelled. Every hit along the way goes into `hits`
+// with the index of the obstacle.
+pub fn move_and_slide(mover: &mut MovingRect, obstacles: &[Rect], hits: &mut Vec<(usize, Hit)>) {
+    let mut motion = *mover;
+    // each hit takes away one axis of motion, so this is plenty
+    for _ in 0..4 {
+        let (index, hit) = match sweep_rects(&motion, obstacles) {
+            Some(found) => found,
+            None => {
+                motion.x += motion.vel.x;
+                motion.y += motion.vel.y;
+                break;
+            }
+        };
+        motion.x += motion.vel.x * hit.time;
+        motion.y += motion.vel.y * hit.time;
+        motion.vel.x *= 1.0 - hit.time;
+        motion.vel.y *= 1.0 - hit.time;
+        cancel_velocity(&mut motion, hit.normal);
+        cancel_velocity(mover, hit.normal);
+        hits.push((index, hit));
+    }
+    mover.x = motion.x;
+    mover.y = motion.y;
+}
+
+// What a ray (or segment) ran into first: `index` is which collider,
+// `distance` is how far from the origin, `point` is where and `normal`
+// points out of the side that got hit. Rays starting inside a collider hit
+// it right away, with a zero normal. Rays starting on an edge hit at
+// distance 0 off that edge if they point in, and miss if they point away.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct RayHit {
+    pub index: usize,
+    pub distance: f32,
+    pub point: Vec2,
+    pub normal: Vec2,
+}
+
+// Edges count, so clicking right on the border of something picks it
+pub fn point_in_rect(p: Vec2, r: Rect) -> bool {
+    r.contains(p)
+}
+
+// `dir` must already be normalized
+fn ray_rect(index: usize, origin: Vec2, dir: Vec2, max_distance: f32, r: Rect) -> Option<RayHit> {
+    // only strictly inside; origins on an edge go through the slab test,
+    // which tells pointing in from pointing away
+    let inside = r.x < origin.x && origin.x < r.right() && r.y < origin.y && origin.y < r.bottom();
+    if inside {
+        return Some(RayHit {
+            index,
+            distance: 0.0,
+            point: origin,
+            normal: Vec2::new(0.0, 0.0),
+        });
+    }
+    // a ray is just a zero-sized box being swept
+    let (x_entry, x_exit) = sweep_axis(origin.x, origin.x, dir.x, r.x, r.x + r.w)?;
+    let (y_entry, y_exit) = sweep_axis(origin.y, origin.y, dir.y, r.y, r.y + r.h)?;
+    let entry = x_entry.max(y_entry);
+    if entry >= x_exit.min(y_exit) || !(0.0..=max_distance).contains(&entry) {
+        return None;
+    }
+    let normal = if x_entry > y_entry {
+        Vec2::new(-dir.x.signum(), 0.0)
+    } else {
+        Vec2::new(0.0, -dir.y.signum())
+    };
+    Some(RayHit {
+        index,
+        distance: entry,
+        point: origin + dir * entry,
+        normal,
+    })
+}
+
+fn closer(best: Option<RayHit>, hit: RayHit) -> Option<RayHit> {
+    match best {
+        Some(b) if b.distance <= hit.distance => Some(b),
+        _ => Some(hit),
+    }
+}
+
+// Indices of every rect under the point
+pub fn query_point(rects: &[Rect], p: Vec2, into: &mut Vec<usize>) {
+    into.extend((0..rects.len()).filter(|&i| point_in_rect(p, rects[i])));
+}
+
+// Indices of every rect touching `r`
+pub fn query_rect(rects: &[Rect], r: Rect, into: &mut Vec<usize>) {
+    into.extend((0..rects.len()).filter(|&i| rect_touching(r, rects[i])));
+}
+
+// The first rect hit going from `origin` towards `dir` (which doesn't need
+// to be normalized), no further than `max_distance`
+pub fn raycast(rects: &[Rect], origin: Vec2, dir: Vec2, max_distance: f32) -> Option<RayHit> {
+    let dir = dir.try_normalize()?;
+    let mut best = None;
+    for (index, r) in rects.iter().enumerate() {
+        if let Some(hit) = ray_rect(index, origin, dir, max_distance, *r) {
+            best = closer(best, hit);
+        }
+    }
+    best
+}
+
+// Like raycast, but from one point to another; good for line of sight
+pub fn segment_cast(rects: &[Rect], from: Vec2, to: Vec2) -> Option<RayHit> {
+    let d = to - from;
+    raycast(rects, from, d, d.length())
+}
+
+// What a single cell of a tile grid does to things moving through it
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TileShape {
+    Empty,
+    Solid,
+    // Only stops things coming down onto its top edge
+    OneWay,
+    // A floor whose height goes from `left` to `right` across the tile,
+    // as fractions of the tile height (0 is the bottom edge, 1 the top)
+    Slope { left: f32, right: f32 },
+}
+
+// Anything laid out on a uniform grid of tiles, like a tilemap::Tilemap
+pub trait TileGrid {
+    fn tile_size(&self) -> Vec2;
+    // Cells off the edge of the grid should just be Empty (or Solid, to wall it in)
+    fn shape_at(&self, col: i32, row: i32) -> TileShape;
+}
+
+// Which sides of the mover ran into something; `bottom` means it's standing on ground
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
+pub struct Sides {
+    pub left: bool,
+    pub right: bool,
+    pub top: bool,
+    pub bottom: bool,
+}
+
+impl Sides {
+    pub fn any(&self) -> bool {
+        self.left || self.right || self.top || self.bottom
+    }
+}
+
+// Touching edges don't count as overlapping
+const EPSILON: f32 = 0.001;
+
+// First and last cells (inclusive) that the span lo..hi overlaps
+fn cell_span(lo: f32, hi: f32, size: f32) -> (i32, i32) {
+    (
+        (lo / size).floor() as i32,
+        ((hi - EPSILON) / size).floor() as i32,
+    )
+}
+
+// Highest point of the slope in cell (col, row) under the span x0..x1
+fn slope_top(left: f32, right: f32, col: i32, row: i32, x0: f32, x1: f32, tile: Vec2) -> f32 {
+    let surface = |x: f32| {
+        let t = ((x - col as f32 * tile.x) / tile.x).clamp(0.0, 1.0);
+        (row + 1) as f32 * tile.y - (left + (right - left) * t) * tile.y
+    };
+    // it's a straight line, so the highest point is at one end
+    let x0 = x0.max(col as f32 * tile.x);
+    let x1 = x1.min((col + 1) as f32 * tile.x);
+    surface(x0).min(surface(x1))
+}
+
+// Moves `mover` by its velocity through the grid, one axis at a time (x then y),
+// stopping it flush against whatever it hits and zeroing that part of its velocity.
+// This uses screen coordinates, so positive vel.y moves down.
+// Walls stop it sideways; one-way tiles and slopes only hold it up from below.
+pub fn move_against_tiles<G: TileGrid>(mover: &mut MovingRect, grid: &G) -> Sides {
+    let tile = grid.tile_size();
+    let mut sides = Sides::default();
+
+    // Horizontal: check every column the leading edge enters, nearest first
+    let dx = mover.vel.x;
+    let (row0, row1) = cell_span(mover.y, mover.y + mover.h, tile.y);
+    let blocked = |col: i32| (row0..=row1).any(|row| grid.shape_at(col, row) == TileShape::Solid);
+    if dx > 0.0 {
+        let first = ((mover.x + mover.w - EPSILON) / tile.x).floor() as i32 + 1;
+        let last = ((mover.x + mover.w + dx - EPSILON) / tile.x).floor() as i32;
+        match (first..=last).find(|&col| blocked(col)) {
+            Some(col) => {
+                mover.x = col as f32 * tile.x - mover.w;
+                mover.vel.x = 0.0;
+                sides.right = true;
+            }
+            None => mover.x += dx,
+        }
+    } else if dx < 0.0 {
+        let first = (mover.x / tile.x).floor() as i32 - 1;
+        let last = ((mover.x + dx) / tile.x).floor() as i32;
+        match (last..=first).rev().find(|&col| blocked(col)) {
+            Some(col) => {
+                mover.x = (col + 1) as f32 * tile.x;
+                mover.vel.x = 0.0;
+                sides.left = true;
+            }
+            None => mover.x += dx,
+        }
+    }
+
+    // Vertical: same idea, but floors can also be one-way tiles or slopes
+    let dy = mover.vel.y;
+    let (col0, col1) = cell_span(mover.x, mover.x + mover.w, tile.x);
+    let (left_x, right_x) = (mover.x, mover.x + mover.w);
+    if dy > 0.0 {
+        let bottom = mover.y + mover.h;
+        let new_bottom = bottom + dy;
+        // start in the row we're already in, since a slope there can still catch us
+        let first = ((bottom - EPSILON) / tile.y).floor() as i32;
+        let last = ((new_bottom - EPSILON) / tile.y).floor() as i32;
+        let mut floor = None;
+        for row in first..=last {
+            let top = row as f32 * tile.y;
+            for col in col0..=col1 {
+                let y = match grid.shape_at(col, row) {
+                    TileShape::Solid | TileShape::OneWay if bottom <= top + EPSILON => Some(top),
+                    TileShape::Slope { left, right } => {
+                        let surface = slope_top(left, right, col, row, left_x, right_x, tile);
+                        if bottom <= surface + EPSILON {
+                            Some(surface)
+                        } else {
+                            None
+                        }
+                    }
+                    _ => None,
+                };
+                if let Some(y) = y.filter(|&y| y <= new_bottom) {
+                    floor = Some(floor.map_or(y, |f: f32| f.min(y)));
+                }
+            }
+            if floor.is_some() {
+                break;
+            }
+        }
+        match floor {
+            Some(y) => {
+                mover.y = y - mover.h;
+                mover.vel.y = 0.0;
+                sides.bottom = true;
+            }
+            None => mover.y += dy,
+        }
+    } else if dy < 0.0 {
+        let first = (mover.y / tile.y).floor() as i32 - 1;
+        let last = ((mover.y + dy) / tile.y).floor() as i32;
+        let hit = (last..=first)
+            .rev()
+            .find(|&row| (col0..=col1).any(|col| grid.shape_at(col, row) == TileShape::Solid));
+        match hit {
+            Some(row) => {
+                mover.y = (row + 1) as f32 * tile.y;
+                mover.vel.y = 0.0;
+                sides.top = true;
+            }
+            None => mover.y += dy,
+        }
+    }
+
+    // Walking along a slope: climb up out of it, or stay stuck to it going
+    // down instead of bouncing off the top of every tile (and onto the floor
+    // at the bottom, which is as far below as the step was long)
+    if dy >= 0.0 {
+        let bottom = mover.y + mover.h;
+        let first = ((bottom - EPSILON) / tile.y).floor() as i32;
+        let (mut climb, mut stick) = (None, None);
+        for row in first..=first + 1 {
+            for col in col0..=col1 {
+                let (y, slope) = match grid.shape_at(col, row) {
+                    TileShape::Slope { left, right } => (
+                        slope_top(left, right, col, row, left_x, right_x, tile),
+                        true,
+                    ),
+                    TileShape::Solid | TileShape::OneWay => (row as f32 * tile.y, false),
+                    TileShape::Empty => continue,
+                };
+                if y < bottom - EPSILON {
+                    // a flat top above our feet is a wall, not something to climb
+                    if slope {
+                        climb = Some(climb.map_or(y, |c: f32| c.min(y)));
+                    }
+                } else if y <= bottom + dx.abs() + EPSILON {
+                    stick = Some(stick.map_or(y, |s: f32| s.min(y)));
+                }
+            }
+        }
+        // don't get pulled down a slope when there's already ground underfoot
+        let floor = climb.or(if sides.bottom { None } else { stick });
+        if let Some(y) = floor {
+            mover.y = y - mover.h;
+            mover.vel.y = 0.0;
+            sides.bottom = true;
+        }
+    }
+    sides
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn scatter(count: usize, world: f32, rng: &mut StdRng) -> Vec<Rect> {
+        (0..count)
+            .map(|_| {
+                let w = rng.gen_range(1.0..40.0);
+                let h = rng.gen_range(1.0..40.0);
+                Rect::new(
+                    rng.gen_range(-world..world),
+                    rng.gen_range(-world..world),
+                    w,
+                    h,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn hash_agrees_with_brute_force() {
+        let mut rng = StdRng::seed_from_u64(2021);
+        let mut hash = SpatialHash::new(16.0);
+        for &count in [0, 1, 50, 500].iter() {
+            let mut rects = scatter(count, 200.0, &mut rng);
+            // some exactly touching, which both count
+            if count > 1 {
+                let r = rects[0];
+                rects.push(Rect::new(r.right(), r.y, 5.0, 5.0));
+                rects.push(Rect::new(r.x, r.bottom(), 5.0, 5.0));
+            }
+            let mut expected = vec![];
+            brute_force_pairs(&rects, &mut expected);
+            hash.rebuild(&rects);
+            let mut found = vec![];
+            hash.pairs(&mut found);
+            found.sort_unstable();
+            assert_eq!(found, expected);
+
+            for r in scatter(20, 200.0, &mut rng) {
+                let (mut brute, mut hashed) = (vec![], vec![]);
+                query_rect(&rects, r, &mut brute);
+                hash.query(r, &mut hashed);
+                assert_eq!(hashed, brute);
+            }
+        }
+    }
+
+    #[test]
+    fn clearing_drops_empty_cells() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut hash = SpatialHash::new(16.0);
+        // the same few rects drifting across a huge world
+        let mut rects = scatter(20, 100.0, &mut rng);
+        for _ in 0..200 {
+            for r in rects.iter_mut() {
+                *r = r.translate(Vec2::new(50.0, 30.0));
+            }
+            hash.rebuild(&rects);
+            assert_eq!(hash.cells.len(), hash.occupied.len());
+            assert!(hash.cells.values().all(|bucket| !bucket.is_empty()));
+        }
+        hash.clear();
+        assert!(hash.cells.is_empty() && hash.is_empty());
+    }
+
+    fn ray(origin: (f32, f32), dir: (f32, f32), max_distance: f32) -> Option<RayHit> {
+        let rects = [Rect::new(10.0, 10.0, 10.0, 10.0)];
+        raycast(&rects, origin.into(), dir.into(), max_distance)
+    }
+
+    fn hit(distance: f32, point: (f32, f32), normal: (f32, f32)) -> Option<RayHit> {
+        Some(RayHit {
+            index: 0,
+            distance,
+            point: point.into(),
+            normal: normal.into(),
+        })
+    }
+
+    #[test]
+    fn rays_hit_the_near_side() {
+        assert_eq!(
+            ray((0.0, 15.0), (1.0, 0.0), 100.0),
+            hit(10.0, (10.0, 15.0), (-1.0, 0.0))
+        );
+        assert_eq!(
+            ray((15.0, 30.0), (0.0, -1.0), 100.0),
+            hit(10.0, (15.0, 20.0), (0.0, 1.0))
+        );
+        let dir = Vec2::new(1.0, 1.0).normalize();
+        let diagonal = ray((0.0, 5.0), (dir.x, dir.y), 100.0).unwrap();
+        assert_eq!(diagonal.normal, Vec2::new(-1.0, 0.0));
+        assert!(diagonal.point.distance(Vec2::new(10.0, 15.0)) < 1e-4);
+    }
+
+    #[test]
+    fn rays_miss() {
+        // passing by, pointing away, and stopping short
+        assert_eq!(ray((0.0, 0.0), (1.0, 0.0), 100.0), None);
+        assert_eq!(ray((0.0, 15.0), (-1.0, 0.0), 100.0), None);
+        assert_eq!(ray((0.0, 15.0), (1.0, 0.0), 9.0), None);
+        // running along an edge
+        assert_eq!(ray((0.0, 10.0), (1.0, 0.0), 100.0), None);
+    }
+
+    #[test]
+    fn rays_from_inside_hit_at_once() {
+        assert_eq!(
+            ray((15.0, 12.0), (0.0, 1.0), 100.0),
+            hit(0.0, (15.0, 12.0), (0.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn rays_from_an_edge() {
+        // pointing away from the edge they start on
+        assert_eq!(ray((10.0, 15.0), (-1.0, 0.0), 100.0), None);
+        assert_eq!(ray((20.0, 15.0), (1.0, 0.0), 100.0), None);
+        assert_eq!(ray((15.0, 10.0), (0.0, -1.0), 100.0), None);
+        assert_eq!(ray((15.0, 20.0), (0.0, 1.0), 100.0), None);
+        // pointing in
+        assert_eq!(
+            ray((10.0, 15.0), (1.0, 0.0), 100.0),
+            hit(0.0, (10.0, 15.0), (-1.0, 0.0))
+        );
+        assert_eq!(
+            ray((15.0, 20.0), (0.0, -1.0), 100.0),
+            hit(0.0, (15.0, 20.0), (0.0, 1.0))
+        );
+    }
+
+    // 10x10 tiles drawn as text: # solid, - one-way, / and \ slopes up to
+    // the right and up to the left, anything else empty
+    struct Grid(Vec<&'static str>);
+
+    impl TileGrid for Grid {
+        fn tile_size(&self) -> Vec2 {
+            Vec2::new(10.0, 10.0)
+        }
+
+        fn shape_at(&self, col: i32, row: i32) -> TileShape {
+            if col < 0 || row < 0 {
+                return TileShape::Empty;
+            }
+            let cell = self
+                .0
+                .get(row as usize)
+                .and_then(|line| line.as_bytes().get(col as usize));
+            match cell {
+                Some(b'#') => TileShape::Solid,
+                Some(b'-') => TileShape::OneWay,
+                Some(b'/') => TileShape::Slope {
+                    left: 0.0,
+                    right: 1.0,
+                },
+                Some(b'\\') => TileShape::Slope {
+                    left: 1.0,
+                    right: 0.0,
+                },
+                _ => TileShape::Empty,
+            }
+        }
+    }
+
+    #[test]
+    fn landing_on_a_floor() {
+        let grid = Grid(vec!["....", "...#", "####"]);
+        let mut mover = MovingRect::new(1.0, 5.0, 8.0, 8.0, Vec2::new(0.0, 10.0));
+        let sides = move_against_tiles(&mut mover, &grid);
+        assert_eq!(
+            sides,
+            Sides {
+                bottom: true,
+                ..Sides::default()
+            }
+        );
+        assert_eq!((mover.y, mover.vel.y), (12.0, 0.0));
+
+        // then walking into the wall
+        mover.vel = Vec2::new(25.0, 1.0);
+        let sides = move_against_tiles(&mut mover, &grid);
+        assert_eq!(
+            sides,
+            Sides {
+                right: true,
+                bottom: true,
+                ..Sides::default()
+            }
+        );
+        assert_eq!((mover.x, mover.y, mover.vel.x), (22.0, 12.0, 0.0));
+    }
+
+    #[test]
+    fn one_way_tiles_only_hold_from_above() {
+        let grid = Grid(vec!["....", "----", "...."]);
+        // jumping up through it
+        let mut mover = MovingRect::new(1.0, 22.0, 8.0, 8.0, Vec2::new(0.0, -15.0));
+        let sides = move_against_tiles(&mut mover, &grid);
+        assert!(!sides.any());
+        assert_eq!((mover.y, mover.vel.y), (7.0, -15.0));
+        // and coming back down onto it
+        mover.vel.y = 5.0;
+        let sides = move_against_tiles(&mut mover, &grid);
+        assert!(!sides.any());
+        assert_eq!(mover.y, 12.0);
+        let mut mover = MovingRect::new(1.0, 0.0, 8.0, 8.0, Vec2::new(0.0, 5.0));
+        let sides = move_against_tiles(&mut mover, &grid);
+        assert!(sides.bottom);
+        assert_eq!((mover.y, mover.vel.y), (2.0, 0.0));
+        // walking sideways through it doesn't bump into anything
+        let mut mover = MovingRect::new(1.0, 8.0, 8.0, 8.0, Vec2::new(20.0, 0.0));
+        assert!(!move_against_tiles(&mut mover, &grid).any());
+        assert_eq!(mover.x, 21.0);
+    }
+
+    #[test]
+    fn walking_up_and_down_a_slope() {
+        let grid = Grid(vec!["....", "../#", "####"]);
+        // a 4x4 mover's feet follow the slope's highest point under it
+        let floor = |x: f32| 40.0 - (x + 4.0).clamp(20.0, 30.0);
+        let mut mover = MovingRect::new(12.0, 16.0, 4.0, 4.0, Vec2::zero());
+        for dx in [2.0, -2.0].iter() {
+            for _ in 0..10 {
+                mover.vel = Vec2::new(*dx, mover.vel.y + 1.0);
+                let sides = move_against_tiles(&mut mover, &grid);
+                assert!(sides.bottom, "fell off at x = {}", mover.x);
+                assert_eq!(mover.y + mover.h, floor(mover.x), "at x = {}", mover.x);
+            }
+            assert_eq!(mover.x, if *dx > 0.0 { 32.0 } else { 12.0 });
+        }
+    }
+
+    fn slide(mover: &mut MovingRect, obstacles: &[Rect]) -> Vec<(usize, Hit)> {
+        let mut hits = vec![];
+        move_and_slide(mover, obstacles, &mut hits);
+        hits
+    }
+
+    fn hit_at(time: f32, normal: (f32, f32)) -> Hit {
+        Hit {
+            time,
+            normal: normal.into(),
+        }
+    }
+
+    #[test]
+    fn fast_movers_stop_at_thin_walls() {
+        let wall = [Rect::new(50.0, -20.0, 1.0, 50.0)];
+        let mut mover = MovingRect::new(0.0, 0.0, 10.0, 10.0, Vec2::new(100.0, 0.0));
+        assert_eq!(
+            slide(&mut mover, &wall),
+            vec![(0, hit_at(0.4, (-1.0, 0.0)))]
+        );
+        assert_eq!((mover.x, mover.vel), (40.0, Vec2::zero()));
+        // rounding left it a hair inside the wall; it still counts as a hit
+        let mut mover = MovingRect::new(40.0005, 0.0, 10.0, 10.0, Vec2::new(5.0, 0.0));
+        assert_eq!(
+            slide(&mut mover, &wall),
+            vec![(0, hit_at(0.0, (-1.0, 0.0)))]
+        );
+        assert_eq!(mover.vel, Vec2::zero());
+    }
+
+    #[test]
+    fn sliding_along_a_floor_doesnt_hit_it() {
+        let floor = [Rect::new(-100.0, 10.0, 200.0, 10.0)];
+        let mut mover = MovingRect::new(0.0, 0.0, 10.0, 10.0, Vec2::new(5.0, 0.0));
+        assert!(slide(&mut mover, &floor).is_empty());
+        assert_eq!(
+            (mover.x, mover.y, mover.vel),
+            (5.0, 0.0, Vec2::new(5.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn moving_away_from_a_surface_doesnt_hit_it() {
+        let floor = [Rect::new(-100.0, 10.0, 200.0, 10.0)];
+        let mut mover = MovingRect::new(0.0, 0.0, 10.0, 10.0, Vec2::new(0.0, -5.0));
+        assert!(slide(&mut mover, &floor).is_empty());
+        assert_eq!((mover.y, mover.vel), (-5.0, Vec2::new(0.0, -5.0)));
+        let wall = [Rect::new(10.0, -100.0, 10.0, 200.0)];
+        let mut mover = MovingRect::new(0.0, 0.0, 10.0, 10.0, Vec2::new(-3.0, 2.0));
+        assert!(slide(&mut mover, &wall).is_empty());
+        assert_eq!((mover.x, mover.y), (-3.0, 2.0));
+    }
+
+    #[test]
+    fn diagonal_moves_into_a_corner_hit_both_sides() {
+        let obstacles = [
+            Rect::new(-100.0, 20.0, 200.0, 10.0),
+            Rect::new(20.0, -100.0, 10.0, 200.0),
+        ];
+        // reaches the wall halfway, then slides down onto the floor
+        let mut mover = MovingRect::new(0.0, 0.0, 10.0, 10.0, Vec2::new(20.0, 10.0));
+        assert_eq!(
+            slide(&mut mover, &obstacles),
+            vec![(1, hit_at(0.5, (-1.0, 0.0))), (0, hit_at(1.0, (0.0, -1.0)))]
+        );
+        assert_eq!((mover.x, mover.y, mover.vel), (10.0, 10.0, Vec2::zero()));
+        // straight into the corner, both sides start touching at once and
+        // the floor counts first
+        let mut mover = MovingRect::new(0.0, 0.0, 10.0, 10.0, Vec2::new(20.0, 20.0));
+        assert_eq!(
+            slide(&mut mover, &obstacles),
+            vec![(0, hit_at(0.5, (0.0, -1.0))), (1, hit_at(0.0, (-1.0, 0.0)))]
+        );
+        assert_eq!((mover.x, mover.y, mover.vel), (10.0, 10.0, Vec2::zero()));
+    }
+
+    // Gathers and restitutes, returning the touches
+    fn push_apart(statics: &[Rect], dynamics: &mut [MovingRect]) -> Vec<Overlap> {
+        let (mut overlaps, mut touches) = (vec![], vec![]);
+        gather_overlaps(statics, dynamics, &mut overlaps);
+        restitute(statics, dynamics, &mut overlaps, &mut touches);
+        touches
+    }
+
+    fn touch(b: ColliderID, mtv: (f32, f32)) -> Overlap {
+        Overlap {
+            a: ColliderID::Dynamic(0),
+            b,
+            mtv: mtv.into(),
+        }
+    }
+
+    #[test]
+    fn biggest_overlaps_go_first() {
+        let statics = [
+            Rect::new(8.0, -10.0, 10.0, 30.0),
+            Rect::new(-10.0, 7.0, 30.0, 10.0),
+        ];
+        let mut dynamics = [MovingRect::new(0.0, 0.0, 10.0, 10.0, Vec2::zero())];
+        let mut overlaps = vec![];
+        gather_overlaps(&statics, &dynamics, &mut overlaps);
+        assert_eq!(
+            overlaps,
+            vec![
+                touch(ColliderID::Static(0), (-2.0, 0.0)),
+                touch(ColliderID::Static(1), (0.0, -3.0)),
+            ]
+        );
+        let mut touches = vec![];
+        restitute(&statics, &mut dynamics, &mut overlaps, &mut touches);
+        assert_eq!(overlaps[0].b, ColliderID::Static(1));
+        assert_eq!(
+            touches,
+            vec![
+                touch(ColliderID::Static(1), (0.0, -3.0)),
+                touch(ColliderID::Static(0), (-2.0, 0.0)),
+            ]
+        );
+        assert_eq!((dynamics[0].x, dynamics[0].y), (-2.0, -3.0));
+    }
+
+    #[test]
+    fn dynamic_pairs_split_the_push() {
+        let mut dynamics = [
+            MovingRect::new(0.0, 0.0, 10.0, 10.0, Vec2::new(5.0, 1.0)),
+            MovingRect::new(8.0, 0.0, 10.0, 10.0, Vec2::new(-5.0, 1.0)),
+        ];
+        assert_eq!(
+            push_apart(&[], &mut dynamics),
+            vec![touch(ColliderID::Dynamic(1), (-1.0, 0.0))]
+        );
+        assert_eq!(
+            (dynamics[0].x, dynamics[0].vel),
+            (-1.0, Vec2::new(0.0, 1.0))
+        );
+        assert_eq!((dynamics[1].x, dynamics[1].vel), (9.0, Vec2::new(0.0, 1.0)));
+    }
+
+    #[test]
+    fn touches_hold_the_pushes_actually_made() {
+        // pushing out of the lower floor also clears the upper one
+        let floors = [
+            Rect::new(-10.0, 8.0, 30.0, 10.0),
+            Rect::new(-10.0, 9.0, 30.0, 10.0),
+        ];
+        let mut dynamics = [MovingRect::new(0.0, 0.0, 10.0, 10.0, Vec2::new(0.0, 3.0))];
+        assert_eq!(
+            push_apart(&floors, &mut dynamics),
+            vec![touch(ColliderID::Static(0), (0.0, -2.0))]
+        );
+        assert_eq!((dynamics[0].y, dynamics[0].vel), (-2.0, Vec2::zero()));
+        // after the wall's push the box is only in the block's corner, so it
+        // goes out sideways instead of up
+        let statics = [
+            Rect::new(6.0, -20.0, 10.0, 50.0),
+            Rect::new(5.0, 7.0, 5.0, 10.0),
+        ];
+        let mut dynamics = [MovingRect::new(0.0, 0.0, 10.0, 10.0, Vec2::zero())];
+        assert_eq!(
+            push_apart(&statics, &mut dynamics),
+            vec![
+                touch(ColliderID::Static(0), (-4.0, 0.0)),
+                touch(ColliderID::Static(1), (-1.0, 0.0)),
+            ]
+        );
+        assert_eq!((dynamics[0].x, dynamics[0].y), (-5.0, 0.0));
+    }
+
+    #[test]
+    fn contact_tracker_follows_pairs_across_frames() {
+        use ContactEvent::*;
+        let pair = |a, b| Contact {
+            a,
+            b,
+            a_index: 0,
+            b_index: 0,
+            trigger: false,
+        };
+        let mut tracker = ContactTracker::new();
+        let mut step = |contacts: &[Contact<char>]| {
+            tracker.update(contacts);
+            tracker.events().collect::<Vec<_>>()
+        };
+        assert_eq!(step(&[pair('a', 'b')]), vec![Began('a', 'b')]);
+        assert_eq!(
+            step(&[pair('a', 'b'), pair('a', 'c')]),
+            vec![Persisted('a', 'b'), Began('a', 'c')]
+        );
+        assert_eq!(
+            step(&[pair('a', 'c')]),
+            vec![Persisted('a', 'c'), Ended('a', 'b')]
+        );
+        assert_eq!(
+            step(&[pair('a', 'b')]),
+            vec![Began('a', 'b'), Ended('a', 'c')]
+        );
+        assert!(tracker.began('a', 'b') && tracker.is_touching('a', 'b'));
+        assert!(tracker.ended('a', 'c') && !tracker.persisted('a', 'c'));
+        tracker.update(&[]);
+        tracker.update(&[]);
+        assert_eq!(tracker.events().count(), 0);
+    }
+
+    #[derive(Copy, Clone, PartialEq, Debug)]
+    struct Tagged(char, Layers);
+
+    impl HasLayers for Tagged {
+        fn layers(&self) -> Layers {
+            self.1
+        }
+    }
+
+    #[test]
+    fn layers_filter_pairs() {
+        let player = Tagged('p', Layers::new(1, 2 | 4 | 8));
+        let obstacles = [
+            // wants the player back
+            Tagged('e', Layers::new(2, 1)),
+            // the player wants it, but it doesn't want the player
+            Tagged('w', Layers::new(4, 0)),
+            // nothing in common
+            Tagged('x', Layers::new(16, 16)),
+            Tagged('t', Layers::trigger(8, 1)),
+        ];
+        let movers = [MovingRect::new(0.0, 0.0, 10.0, 10.0, Vec2::zero())];
+        let rects = [Rect::new(5.0, 5.0, 10.0, 10.0); 4];
+        let contacts = gather_layered_contacts(&movers, &[player], &rects, &obstacles);
+        let found: Vec<_> = contacts.iter().map(|c| (c.b.0, c.trigger)).collect();
+        assert_eq!(found, vec![('e', false), ('t', true)]);
+
+        let layers: Vec<_> = obstacles.iter().map(|o| o.1).collect();
+        let (mut into, mut triggers) = (vec![], vec![]);
+        gather_layered_overlaps(
+            &rects,
+            &layers,
+            &movers,
+            &[player.1],
+            &mut into,
+            &mut triggers,
+        );
+        assert_eq!(
+            into.iter().map(|o| o.b).collect::<Vec<_>>(),
+            vec![ColliderID::Static(0)]
+        );
+        assert_eq!(
+            triggers.iter().map(|o| o.b).collect::<Vec<_>>(),
+            vec![ColliderID::Static(3)]
+        );
+    }
+}
@@ -0,0 +1,130 @@
+use crate::input::Input;
+use crate::screen::Screen;
+use pixels::{Pixels, SurfaceTexture};
+use std::time::Instant;
+use winit::dpi::LogicalSize;
+use winit::event::{Event, VirtualKeyCode};
+use winit::event_loop::{ControlFlow, EventLoop};
+use winit::window::WindowBuilder;
+use winit_input_helper::WinitInputHelper;
+
+const DEPTH: usize = 4;
+// After a long stall (dragging the window, a breakpoint...) don't try to
+// catch up on more than this many seconds of ticks at once
+const MAX_CATCH_UP: f64 = 0.25;
+
+pub struct Config {
+    pub title: String,
+    // The size of the framebuffer games draw into
+    pub width: usize,
+    pub height: usize,
+    // How many window pixels each framebuffer pixel takes up
+    pub scale: f64,
+    // Simulation ticks per second
+    pub tick_rate: f64,
+    pub escape_quits: bool,
+}
+
+impl Config {
+    pub fn new(title: &str, width: usize, height: usize) -> Self {
+        Self {
+            title: title.to_string(),
+            width,
+            height,
+            scale: 1.0,
+            tick_rate: 60.0,
+            escape_quits: true,
+        }
+    }
+}
+
+pub trait Game {
+    type ActionID: Ord + Eq;
+
+    // One fixed-length tick of the simulation
+    fn update(&mut self, input: &Input<Self::ActionID>);
+
+    // `alpha` is how far along (0.0 to 1.0) the next tick we are, for
+    // smoothing things out between ticks; it's fine to ignore it
+    fn draw(&self, screen: &mut Screen, alpha: f32);
+
+    // Checked after every batch of ticks; return false to quit
+    fn running(&self) -> bool {
+        true
+    }
+}
+
+// Opens the window and runs the game until it quits. Time that passes
+// between frames gets eaten by as many update() ticks as fit in it, and
+// whatever is left over becomes the alpha given to draw().
+pub fn run<G>(config: Config, mut input: Input<G::ActionID>, mut game: G) -> !
+where
+    G: Game + 'static,
+    G::ActionID: 'static,
+{
+    let event_loop = EventLoop::new();
+    let mut events = WinitInputHelper::new();
+    let window = {
+        let size = LogicalSize::new(
+            config.width as f64 * config.scale,
+            config.height as f64 * config.scale,
+        );
+        WindowBuilder::new()
+            .with_title(&config.title)
+            .with_inner_size(size)
+            .with_min_inner_size(size)
+            .with_resizable(false)
+            .build(&event_loop)
+            .unwrap()
+    };
+    let mut pixels = {
+        let window_size = window.inner_size();
+        let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
+        Pixels::new(config.width as u32, config.height as u32, surface_texture).unwrap()
+    };
+    let dt = 1.0 / config.tick_rate;
+    // How much unsimulated time have we saved up?
+    let mut available_time = 0.0;
+    // Track end of the last frame
+    let mut since = Instant::now();
+    event_loop.run(move |event, _, control_flow| {
+        // Draw the current frame
+        if let Event::RedrawRequested(_) = event {
+            let mut screen = Screen::wrap(pixels.get_frame(), config.width, config.height, DEPTH);
+            game.draw(&mut screen, (available_time / dt) as f32);
+            // Flip buffers
+            if pixels.render().is_err() {
+                *control_flow = ControlFlow::Exit;
+                return;
+            }
+        }
+        // Handle input events; this is true once all of a frame's events are in
+        if events.update(&event) {
+            // Close events
+            if events.quit() || (config.escape_quits && events.key_pressed(VirtualKeyCode::Escape))
+            {
+                *control_flow = ControlFlow::Exit;
+                return;
+            }
+            // Resize the window if needed
+            if let Some(size) = events.window_resized() {
+                pixels.resize(size.width, size.height);
+            }
+            // The renderer "produces" time...
+            available_time = (available_time + since.elapsed().as_secs_f64()).min(MAX_CATCH_UP);
+            since = Instant::now();
+            // ...and the simulation "consumes" it
+            while available_time >= dt {
+                available_time -= dt;
+                // Presses only show up on the first tick of a batch
+                input.update(&events);
+                game.update(&input);
+            }
+            if !game.running() {
+                *control_flow = ControlFlow::Exit;
+                return;
+            }
+            window.request_redraw();
+        }
+    });
+}
@@ -8,6 +8,12 @@ pub struct Input<ActionID: Ord + Eq> {
     last_frame_keys: BTreeSet<VirtualKeyCode>,
 }
 
+impl<ActionID: Ord + Eq> Default for Input<ActionID> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<ActionID: Ord + Eq> Input<ActionID> {
     pub fn new() -> Self {
         Self {
@@ -32,7 +38,7 @@ impl<ActionID: Ord + Eq> Input<ActionID> {
 
     pub fn is_held(&self, id: ActionID) -> bool {
         if let Some(key) = self.key_map.get(&id) {
-            self.this_frame_keys.contains(&key)
+            self.this_frame_keys.contains(key)
         } else {
             false
         }
@@ -40,7 +46,7 @@ impl<ActionID: Ord + Eq> Input<ActionID> {
 
     pub fn is_pressed(&self, id: ActionID) -> bool {
         if let Some(key) = self.key_map.get(&id) {
-            self.this_frame_keys.contains(&key) && !self.last_frame_keys.contains(&key)
+            self.this_frame_keys.contains(key) && !self.last_frame_keys.contains(key)
         } else {
             false
         }
@@ -48,7 +54,7 @@ impl<ActionID: Ord + Eq> Input<ActionID> {
 
     pub fn is_released(&self, id: ActionID) -> bool {
         if let Some(key) = self.key_map.get(&id) {
-            !self.this_frame_keys.contains(&key) && self.last_frame_keys.contains(&key)
+            !self.this_frame_keys.contains(key) && self.last_frame_keys.contains(key)
         } else {
             false
         }
@@ -1,8 +1,20 @@
 pub mod animation;
+pub mod animator;
+pub mod atlas;
+pub mod camera;
+pub mod canvas;
 pub mod collision;
+pub mod game;
 pub mod input;
+pub mod mask;
 pub mod objects;
+pub mod parallax;
+pub mod scene;
 pub mod screen;
+pub mod shape;
 pub mod sprite;
+pub mod spritesheet;
 pub mod text;
 pub mod texture;
+pub mod tilemap;
+pub mod transform;
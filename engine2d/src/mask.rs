@@ -0,0 +1,238 @@
+use crate::animation::AnimationData;
+use crate::objects::{Rect, Vec2};
+use crate::texture::Texture;
+
+// One bit per pixel of a sprite frame: set where the frame isn't see-through.
+// Masks are placed in the world by their top-left corner (same as a sprite's
+// position), cut to whole pixels the same way Screen::bitblt does. Sprite
+// transforms aren't applied, so a rotated or flipped sprite needs its own
+// mask.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Mask {
+    width: usize,
+    height: usize,
+    // each row starts on a fresh word
+    words_per_row: usize,
+    bits: Vec<u64>,
+}
+
+impl Mask {
+    pub fn new(width: usize, height: usize) -> Self {
+        let words_per_row = width.div_ceil(64);
+        Self {
+            width,
+            height,
+            words_per_row,
+            bits: vec![0; words_per_row * height],
+        }
+    }
+
+    // Pixels of `frame` with alpha above `threshold` are solid. The alpha
+    // channel is the same premultiplied or not, so this just reads it off.
+    pub fn from_texture(tex: &Texture, frame: Rect, threshold: u8) -> Self {
+        assert!(tex.valid_frame(frame));
+        let (x0, y0) = (frame.x as usize, frame.y as usize);
+        let mut mask = Self::new(frame.w as usize, frame.h as usize);
+        let (buf, pitch, depth) = (tex.buffer(), tex.pitch(), tex.depth());
+        for y in 0..mask.height {
+            for x in 0..mask.width {
+                let alpha = buf[(y0 + y) * pitch + (x0 + x) * depth + depth - 1];
+                if alpha > threshold {
+                    mask.set(x, y, true);
+                }
+            }
+        }
+        mask
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        assert!(x < self.width && y < self.height);
+        self.bits[y * self.words_per_row + x / 64] & (1 << (x % 64)) != 0
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, solid: bool) {
+        assert!(x < self.width && y < self.height);
+        let word = &mut self.bits[y * self.words_per_row + x / 64];
+        if solid {
+            *word |= 1 << (x % 64);
+        } else {
+            *word &= !(1 << (x % 64));
+        }
+    }
+
+    // How many pixels are solid
+    pub fn count(&self) -> usize {
+        self.bits.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    // The smallest rect (relative to the mask) holding every solid pixel,
+    // handy as a tighter broadphase box than the whole frame
+    pub fn bounds(&self) -> Option<Rect> {
+        let mut found: Option<(usize, usize, usize, usize)> = None;
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if self.get(x, y) {
+                    found = Some(match found {
+                        None => (x, y, x, y),
+                        Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+                    });
+                }
+            }
+        }
+        found.map(|(x0, y0, x1, y1)| {
+            Rect::new(
+                x0 as f32,
+                y0 as f32,
+                (x1 - x0 + 1) as f32,
+                (y1 - y0 + 1) as f32,
+            )
+        })
+    }
+
+    // The bits of row `y` from column `x` on, shifted down to bit 0;
+    // columns past the right edge read as empty
+    fn row_bits(&self, x: usize, y: usize) -> u64 {
+        let row = &self.bits[y * self.words_per_row..(y + 1) * self.words_per_row];
+        let (word, shift) = (x / 64, x % 64);
+        let lo = row.get(word).copied().unwrap_or(0) >> shift;
+        let hi = if shift == 0 {
+            0
+        } else {
+            row.get(word + 1).copied().unwrap_or(0) << (64 - shift)
+        };
+        lo | hi
+    }
+}
+
+// Where the blitter puts a sprite drawn at `v`, so masks line up with what's
+// on screen
+fn pixel(v: Vec2) -> (i32, i32) {
+    (v.x as i32, v.y as i32)
+}
+
+// Do any solid pixels of `a` at `a_pos` land on solid pixels of `b` at `b_pos`?
+pub fn mask_overlap(a: &Mask, a_pos: Vec2, b: &Mask, b_pos: Vec2) -> bool {
+    let (ax, ay) = pixel(a_pos);
+    let (bx, by) = pixel(b_pos);
+    // the part of the world both masks cover
+    let x0 = ax.max(bx);
+    let y0 = ay.max(by);
+    let x1 = (ax + a.width as i32).min(bx + b.width as i32);
+    let y1 = (ay + a.height as i32).min(by + b.height as i32);
+    for y in y0..y1 {
+        let (a_row, b_row) = ((y - ay) as usize, (y - by) as usize);
+        // compare 64 pixels at a time
+        let mut x = x0;
+        while x < x1 {
+            let left = (x1 - x).min(64) as u32;
+            let keep = if left == 64 { !0 } else { (1u64 << left) - 1 };
+            let a_bits = a.row_bits((x - ax) as usize, a_row);
+            let b_bits = b.row_bits((x - bx) as usize, b_row);
+            if a_bits & b_bits & keep != 0 {
+                return true;
+            }
+            x += 64;
+        }
+    }
+    false
+}
+
+// Do any solid pixels of `a` at `a_pos` fall inside `r`? Partly covered
+// pixels count.
+pub fn mask_rect_overlap(a: &Mask, a_pos: Vec2, r: Rect) -> bool {
+    let (ax, ay) = pixel(a_pos);
+    let x0 = ax.max(r.x.floor() as i32);
+    let y0 = ay.max(r.y.floor() as i32);
+    let x1 = (ax + a.width as i32).min((r.x + r.w).ceil() as i32);
+    let y1 = (ay + a.height as i32).min((r.y + r.h).ceil() as i32);
+    (y0..y1).any(|y| (x0..x1).any(|x| a.get((x - ax) as usize, (y - ay) as usize)))
+}
+
+// A mask for every frame an animation uses, looked up by the frame's rect
+// (what Animation::get_current_frame gives back).
+pub struct FrameMasks {
+    frames: Vec<(Rect, Mask)>,
+}
+
+impl FrameMasks {
+    pub fn new(tex: &Texture, data: &AnimationData, threshold: u8) -> Self {
+        let mut frames: Vec<(Rect, Mask)> = vec![];
+        for (frame, _) in data.frames.iter() {
+            if !frames.iter().any(|(r, _)| r == frame) {
+                frames.push((*frame, Mask::from_texture(tex, *frame, threshold)));
+            }
+        }
+        Self { frames }
+    }
+
+    pub fn get(&self, frame: Rect) -> Option<&Mask> {
+        self.frames
+            .iter()
+            .find(|(r, _)| *r == frame)
+            .map(|(_, mask)| mask)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: usize, height: usize) -> Mask {
+        let mut mask = Mask::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                mask.set(x, y, true);
+            }
+        }
+        mask
+    }
+
+    #[test]
+    fn touching_masks_dont_overlap() {
+        let (a, b) = (solid(4, 4), solid(4, 4));
+        let at = Vec2::zero();
+        assert!(!mask_overlap(&a, at, &b, Vec2::new(4.0, 0.0)));
+        assert!(!mask_overlap(&a, at, &b, Vec2::new(0.0, 4.0)));
+        assert!(!mask_overlap(&a, at, &b, Vec2::new(4.0, 4.0)));
+        assert!(mask_overlap(&a, at, &b, Vec2::new(3.0, 0.0)));
+        assert!(mask_overlap(&a, at, &b, Vec2::new(-3.0, 3.0)));
+    }
+
+    #[test]
+    fn fractional_positions_land_where_they_draw() {
+        let (a, b) = (solid(4, 4), solid(4, 4));
+        let at = Vec2::zero();
+        // drawn at x = 3, so it covers a's last column
+        assert!(mask_overlap(&a, at, &b, Vec2::new(3.9, 0.0)));
+        assert!(!mask_overlap(&a, at, &b, Vec2::new(4.4, 0.0)));
+        assert!(mask_overlap(
+            &a,
+            Vec2::new(0.7, 0.7),
+            &b,
+            Vec2::new(3.2, 3.2)
+        ));
+    }
+
+    #[test]
+    fn holes_dont_collide() {
+        // a ring with an empty middle, and a dot that fits in it
+        let mut ring = solid(3, 3);
+        ring.set(1, 1, false);
+        let dot = solid(1, 1);
+        assert!(!mask_overlap(
+            &ring,
+            Vec2::zero(),
+            &dot,
+            Vec2::new(1.0, 1.0)
+        ));
+        assert!(mask_overlap(&ring, Vec2::zero(), &dot, Vec2::new(2.0, 1.0)));
+    }
+}
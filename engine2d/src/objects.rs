@@ -1,11 +1,14 @@
 #![allow(dead_code)]
+use serde::{Deserialize, Serialize};
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
 const DEPTH: usize = 4;
 const WIDTH: usize = 240;
 const HEIGHT: usize = 360;
 
 pub type Color = [u8; DEPTH];
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
 pub struct Vec2 {
     pub x: f32,
     pub y: f32,
@@ -15,9 +18,132 @@ impl Vec2 {
     pub fn new(x: f32, y: f32) -> Self {
         Self { x, y }
     }
+
+    pub fn zero() -> Self {
+        Self::new(0.0, 0.0)
+    }
+
+    pub fn dot(self, other: Vec2) -> f32 {
+        self.x * other.x + self.y * other.y
+    }
+
+    // The z of the 3D cross product; positive if `other` is clockwise
+    // from `self` on screen (+y is down)
+    pub fn cross(self, other: Vec2) -> f32 {
+        self.x * other.y - self.y * other.x
+    }
+
+    pub fn length_squared(self) -> f32 {
+        self.dot(self)
+    }
+
+    pub fn length(self) -> f32 {
+        self.length_squared().sqrt()
+    }
+
+    pub fn distance(self, other: Vec2) -> f32 {
+        (other - self).length()
+    }
+
+    // None for the zero vector, which has no direction
+    pub fn try_normalize(self) -> Option<Vec2> {
+        let len = self.length();
+        if len == 0.0 {
+            None
+        } else {
+            Some(self / len)
+        }
+    }
+
+    // Same as try_normalize, but the zero vector stays zero
+    pub fn normalize(self) -> Vec2 {
+        self.try_normalize().unwrap_or_default()
+    }
+
+    // `t` of 0.0 gives self, 1.0 gives `to`
+    pub fn lerp(self, to: Vec2, t: f32) -> Vec2 {
+        self + (to - self) * t
+    }
+
+    // Turned a quarter turn clockwise on screen
+    pub fn perp(self) -> Vec2 {
+        Vec2::new(-self.y, self.x)
+    }
 }
 
-#[derive(Copy, Clone, PartialEq)]
+impl Add for Vec2 {
+    type Output = Vec2;
+    fn add(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl Sub for Vec2 {
+    type Output = Vec2;
+    fn sub(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl Mul<f32> for Vec2 {
+    type Output = Vec2;
+    fn mul(self, s: f32) -> Vec2 {
+        Vec2::new(self.x * s, self.y * s)
+    }
+}
+
+impl Mul<Vec2> for f32 {
+    type Output = Vec2;
+    fn mul(self, v: Vec2) -> Vec2 {
+        v * self
+    }
+}
+
+impl Div<f32> for Vec2 {
+    type Output = Vec2;
+    fn div(self, s: f32) -> Vec2 {
+        Vec2::new(self.x / s, self.y / s)
+    }
+}
+
+impl Neg for Vec2 {
+    type Output = Vec2;
+    fn neg(self) -> Vec2 {
+        Vec2::new(-self.x, -self.y)
+    }
+}
+
+impl AddAssign for Vec2 {
+    fn add_assign(&mut self, other: Vec2) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for Vec2 {
+    fn sub_assign(&mut self, other: Vec2) {
+        *self = *self - other;
+    }
+}
+
+impl MulAssign<f32> for Vec2 {
+    fn mul_assign(&mut self, s: f32) {
+        *self = *self * s;
+    }
+}
+
+impl DivAssign<f32> for Vec2 {
+    fn div_assign(&mut self, s: f32) {
+        *self = *self / s;
+    }
+}
+
+impl From<(f32, f32)> for Vec2 {
+    fn from((x, y): (f32, f32)) -> Self {
+        Vec2::new(x, y)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct MovingRect {
     pub x: f32,
     pub y: f32,
@@ -31,12 +157,43 @@ impl MovingRect {
         Self { x, y, w, h, vel }
     }
 
+    pub fn from_rect(r: Rect, vel: Vec2) -> Self {
+        Self::new(r.x, r.y, r.w, r.h, vel)
+    }
+
     pub fn as_rect(&self) -> Rect {
         Rect::new(self.x, self.y, self.w, self.h)
     }
+
+    pub fn pos(&self) -> Vec2 {
+        Vec2::new(self.x, self.y)
+    }
+
+    pub fn set_pos(&mut self, pos: Vec2) {
+        self.x = pos.x;
+        self.y = pos.y;
+    }
+
+    pub fn translate(&mut self, by: Vec2) {
+        self.x += by.x;
+        self.y += by.y;
+    }
+}
+
+impl From<MovingRect> for Rect {
+    fn from(m: MovingRect) -> Self {
+        m.as_rect()
+    }
+}
+
+// Starts out standing still
+impl From<Rect> for MovingRect {
+    fn from(r: Rect) -> Self {
+        MovingRect::from_rect(r, Vec2::zero())
+    }
 }
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
 pub struct Rect {
     pub x: f32,
     pub y: f32,
@@ -56,6 +213,99 @@ impl Rect {
     pub fn size(&self) -> Vec2 {
         Vec2::new(self.w, self.h)
     }
+
+    pub fn from_pos_size(pos: Vec2, size: Vec2) -> Self {
+        Self::new(pos.x, pos.y, size.x, size.y)
+    }
+
+    // The smallest rect with both points on its edges
+    pub fn from_corners(a: Vec2, b: Vec2) -> Self {
+        let (x0, y0) = (a.x.min(b.x), a.y.min(b.y));
+        Self::new(x0, y0, a.x.max(b.x) - x0, a.y.max(b.y) - y0)
+    }
+
+    pub fn left(&self) -> f32 {
+        self.x
+    }
+
+    pub fn right(&self) -> f32 {
+        self.x + self.w
+    }
+
+    pub fn top(&self) -> f32 {
+        self.y
+    }
+
+    pub fn bottom(&self) -> f32 {
+        self.y + self.h
+    }
+
+    pub fn center(&self) -> Vec2 {
+        Vec2::new(self.x + self.w / 2.0, self.y + self.h / 2.0)
+    }
+
+    // Points on the edges count as inside
+    pub fn contains(&self, p: Vec2) -> bool {
+        self.x <= p.x && p.x <= self.right() && self.y <= p.y && p.y <= self.bottom()
+    }
+
+    pub fn contains_rect(&self, other: Rect) -> bool {
+        self.x <= other.x
+            && other.right() <= self.right()
+            && self.y <= other.y
+            && other.bottom() <= self.bottom()
+    }
+
+    // Touching edges count, the same as collision::rect_touching, so this
+    // is true for rects whose intersection() is None
+    pub fn intersects(&self, other: Rect) -> bool {
+        self.x <= other.right()
+            && other.x <= self.right()
+            && self.y <= other.bottom()
+            && other.y <= self.bottom()
+    }
+
+    // The overlapping part, or None if there's no area to it: rects that
+    // only touch along an edge or at a corner give None even though
+    // intersects() is true for them
+    pub fn intersection(&self, other: Rect) -> Option<Rect> {
+        let x0 = self.x.max(other.x);
+        let y0 = self.y.max(other.y);
+        let x1 = self.right().min(other.right());
+        let y1 = self.bottom().min(other.bottom());
+        if x1 <= x0 || y1 <= y0 {
+            None
+        } else {
+            Some(Rect::new(x0, y0, x1 - x0, y1 - y0))
+        }
+    }
+
+    // The smallest rect holding both
+    pub fn union(&self, other: Rect) -> Rect {
+        let x0 = self.x.min(other.x);
+        let y0 = self.y.min(other.y);
+        Rect::new(
+            x0,
+            y0,
+            self.right().max(other.right()) - x0,
+            self.bottom().max(other.bottom()) - y0,
+        )
+    }
+
+    // Grows every side outwards by `dx` and `dy` (shrinks if negative),
+    // keeping the same center
+    pub fn inflate(&self, dx: f32, dy: f32) -> Rect {
+        Rect::new(
+            self.x - dx,
+            self.y - dy,
+            self.w + dx * 2.0,
+            self.h + dy * 2.0,
+        )
+    }
+
+    pub fn translate(&self, by: Vec2) -> Rect {
+        Rect::new(self.x + by.x, self.y + by.y, self.w, self.h)
+    }
 }
 
 pub fn dist((x0, y0): (i32, i32), (x1, y1): (i32, i32)) -> f32 {
@@ -63,3 +313,61 @@ pub fn dist((x0, y0): (i32, i32), (x1, y1): (i32, i32)) -> f32 {
     let dy = (y0 - y1) as f32;
     (dx * dx + dy * dy).sqrt()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize() {
+        assert_eq!(Vec2::zero().try_normalize(), None);
+        assert_eq!(Vec2::zero().normalize(), Vec2::zero());
+        assert_eq!(Vec2::new(0.0, -3.0).normalize(), Vec2::new(0.0, -1.0));
+        assert_eq!(Vec2::new(3.0, 4.0).normalize(), Vec2::new(0.6, 0.8));
+    }
+
+    #[test]
+    fn perp_turns_clockwise_on_screen() {
+        // right turns to down, down to left
+        assert_eq!(Vec2::new(1.0, 0.0).perp(), Vec2::new(0.0, 1.0));
+        assert_eq!(Vec2::new(0.0, 1.0).perp(), Vec2::new(-1.0, 0.0));
+        let v = Vec2::new(2.0, 5.0);
+        assert_eq!(v.dot(v.perp()), 0.0);
+        assert!(v.cross(v.perp()) > 0.0);
+    }
+
+    #[test]
+    fn touching_rects_intersect_without_an_intersection() {
+        let a = Rect::new(0.0, 0.0, 10.0, 10.0);
+        let beside = Rect::new(10.0, 2.0, 5.0, 5.0);
+        assert!(a.intersects(beside));
+        assert_eq!(a.intersection(beside), None);
+        let corner = Rect::new(10.0, 10.0, 5.0, 5.0);
+        assert!(a.intersects(corner));
+        assert_eq!(a.intersection(corner), None);
+        let apart = Rect::new(10.5, 0.0, 5.0, 5.0);
+        assert!(!a.intersects(apart));
+        let over = Rect::new(8.0, -2.0, 5.0, 5.0);
+        assert!(a.intersects(over));
+        assert_eq!(a.intersection(over), Some(Rect::new(8.0, 0.0, 2.0, 3.0)));
+    }
+
+    #[test]
+    fn union_holds_both() {
+        let a = Rect::new(0.0, 0.0, 4.0, 4.0);
+        let b = Rect::new(6.0, -2.0, 2.0, 3.0);
+        let u = a.union(b);
+        assert_eq!(u, Rect::new(0.0, -2.0, 8.0, 6.0));
+        assert!(u.contains_rect(a) && u.contains_rect(b));
+        assert_eq!(a.union(a), a);
+    }
+
+    #[test]
+    fn inflate_keeps_the_center() {
+        let r = Rect::new(2.0, 4.0, 6.0, 8.0);
+        let grown = r.inflate(1.0, 2.0);
+        assert_eq!(grown, Rect::new(1.0, 2.0, 8.0, 12.0));
+        assert_eq!(grown.center(), r.center());
+        assert_eq!(grown.inflate(-1.0, -2.0), r);
+    }
+}
@@ -0,0 +1,308 @@
+use crate::animation::{Animation, AnimationData};
+use crate::camera::Camera;
+use crate::objects::Vec2;
+use crate::screen::Screen;
+use crate::sprite::{DrawSpriteExt, Sprite};
+use crate::texture::Texture;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use std::rc::Rc;
+
+// One strip of scenery (buildings, clouds, hills...) that keeps spawning
+// random pieces from `spawns` as it scrolls past.
+pub struct Layer {
+    pub texture: Rc<Texture>,
+    pub spawns: Vec<Rc<AnimationData>>,
+    // 1.0 moves with the foreground, smaller is further away
+    pub scroll_factor: f32,
+    // Min and max space between one piece and the next
+    pub gap: (f32, f32),
+    // Top edge of each piece, or its bottom edge if anchor_bottom is set
+    pub y: f32,
+    pub anchor_bottom: bool,
+    // Pieces get moved up to this far from `y` (down, or up when bottom anchored)
+    pub jitter: f32,
+    sprites: Vec<Sprite>,
+    next_x: f32,
+}
+
+impl Layer {
+    pub fn new(
+        texture: &Rc<Texture>,
+        spawns: Vec<Rc<AnimationData>>,
+        scroll_factor: f32,
+        gap: (f32, f32),
+    ) -> Self {
+        Self {
+            texture: Rc::clone(texture),
+            spawns,
+            scroll_factor,
+            gap,
+            y: 0.0,
+            anchor_bottom: false,
+            jitter: 0.0,
+            sprites: Vec::new(),
+            next_x: 0.0,
+        }
+    }
+
+    pub fn sprites(&self) -> &[Sprite] {
+        &self.sprites
+    }
+
+    fn clear<R: Rng>(&mut self, rng: &mut R) {
+        self.sprites.clear();
+        self.next_x = self.random_gap(rng);
+    }
+
+    fn random_gap<R: Rng>(&self, rng: &mut R) -> f32 {
+        if self.gap.0 < self.gap.1 {
+            rng.gen_range(self.gap.0..self.gap.1)
+        } else {
+            self.gap.0
+        }
+    }
+
+    // Drop whatever is left of `left` and spawn until we're past `right`,
+    // both in this layer's own scrolled coordinates
+    fn fill<R: Rng>(&mut self, rng: &mut R, left: f32, right: f32) {
+        self.sprites
+            .retain(|s| s.position.x + s.animation.get_current_frame().w >= left);
+        if self.spawns.is_empty() {
+            return;
+        }
+        while self.next_x < right {
+            let data = &self.spawns[rng.gen_range(0..self.spawns.len())];
+            let frame = data.frames[0].0;
+            let jitter = if self.jitter > 0.0 {
+                rng.gen_range(0.0..self.jitter)
+            } else {
+                0.0
+            };
+            let y = if self.anchor_bottom {
+                self.y - frame.h - jitter
+            } else {
+                self.y + jitter
+            };
+            self.sprites.push(Sprite::new(
+                &self.texture,
+                Animation::new(data),
+                Vec2::new(self.next_x, y),
+            ));
+            self.next_x += frame.w + self.random_gap(rng);
+        }
+    }
+}
+
+// A stack of layers drawn back to front, scrolled by simulation time.
+// All the randomness comes from `seed`, so the same seed and the same
+// sequence of update calls always produce the same scenery.
+// Camera pans scroll each layer by its scroll_factor too; games that pan
+// should say where the camera is with pan_to so the pieces get spawned
+// where it's looking.
+pub struct Parallax {
+    layers: Vec<Layer>,
+    // Foreground scroll speed in pixels per second
+    pub speed: f32,
+    scroll: f32,
+    // the camera's x offset, as of the last pan_to
+    pan: f32,
+    view_width: f32,
+    seed: u64,
+    rng: StdRng,
+}
+
+impl Parallax {
+    pub fn new(view_width: f32, speed: f32, seed: u64) -> Self {
+        Self {
+            layers: Vec::new(),
+            speed,
+            scroll: 0.0,
+            pan: 0.0,
+            view_width,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    // Layers added later draw on top of earlier ones
+    pub fn add_layer(&mut self, mut layer: Layer) {
+        layer.clear(&mut self.rng);
+        let left = (self.pan + self.scroll) * layer.scroll_factor;
+        layer.fill(&mut self.rng, left, left + self.view_width);
+        self.layers.push(layer);
+    }
+
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    // How far the foreground has scrolled so far
+    pub fn scroll(&self) -> f32 {
+        self.scroll
+    }
+
+    // Start over from scroll 0 with the seed it was made with, so the
+    // layers respawn the same way they did the first time
+    pub fn reset(&mut self) {
+        self.scroll = 0.0;
+        self.rng = StdRng::seed_from_u64(self.seed);
+        let (pan, view_width) = (self.pan, self.view_width);
+        for layer in self.layers.iter_mut() {
+            let left = pan * layer.scroll_factor;
+            layer.clear(&mut self.rng);
+            layer.fill(&mut self.rng, left, left + view_width);
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.scroll += self.speed * dt;
+        self.fill();
+    }
+
+    // Where the camera drawing this is panned to. Pieces only spawn ahead,
+    // so panning back left shows gaps where pieces already scrolled off.
+    pub fn pan_to(&mut self, camera_x: f32) {
+        self.pan = camera_x;
+        self.fill();
+    }
+
+    fn fill(&mut self) {
+        let (start, view_width) = (self.pan + self.scroll, self.view_width);
+        for layer in self.layers.iter_mut() {
+            let left = start * layer.scroll_factor;
+            layer.fill(&mut self.rng, left, left + view_width);
+        }
+    }
+
+    // `camera` with the pan and the scroll both slowed down for `layer`
+    fn layer_camera(&self, layer: &Layer, camera: Camera) -> Camera {
+        let offset = Vec2::new(
+            (camera.offset.x + self.scroll) * layer.scroll_factor,
+            camera.offset.y,
+        );
+        Camera { offset, ..camera }
+    }
+
+    // Draws on top of whatever camera the screen already has
+    pub fn draw(&self, screen: &mut Screen) {
+        let camera = screen.camera();
+        for layer in self.layers.iter() {
+            screen.set_camera(self.layer_camera(layer, camera));
+            for sprite in layer.sprites.iter() {
+                screen.draw_sprite(sprite);
+            }
+        }
+        screen.set_camera(camera);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animation::Playback;
+    use crate::objects::Rect;
+    use image::RgbaImage;
+
+    fn parallax(seed: u64) -> Parallax {
+        let texture = Rc::new(Texture::new(RgbaImage::new(32, 32)));
+        let piece = |w: f32, h: f32| {
+            Rc::new(AnimationData::new(
+                vec![(Rect::new(0.0, 0.0, w, h), 1.0)],
+                Playback::Loop,
+            ))
+        };
+        let mut parallax = Parallax::new(100.0, 30.0, seed);
+        let mut far = Layer::new(
+            &texture,
+            vec![piece(8.0, 8.0), piece(16.0, 4.0)],
+            0.5,
+            (2.0, 20.0),
+        );
+        far.jitter = 5.0;
+        parallax.add_layer(far);
+        let mut near = Layer::new(
+            &texture,
+            vec![piece(4.0, 12.0), piece(12.0, 12.0)],
+            1.0,
+            (0.0, 8.0),
+        );
+        near.y = 80.0;
+        near.anchor_bottom = true;
+        near.jitter = 3.0;
+        parallax.add_layer(near);
+        parallax
+    }
+
+    // Where every piece of every layer is, and which frame it shows
+    fn scenery(parallax: &Parallax) -> Vec<Vec<(Vec2, Rect)>> {
+        parallax
+            .layers()
+            .iter()
+            .map(|layer| {
+                layer
+                    .sprites()
+                    .iter()
+                    .map(|s| (s.position, s.animation.get_current_frame()))
+                    .collect()
+            })
+            .collect()
+    }
+
+    const DTS: [f32; 6] = [1.0 / 60.0, 0.5, 0.1, 2.0, 1.0 / 60.0, 3.0];
+
+    #[test]
+    fn same_seed_same_scenery() {
+        let (mut a, mut b) = (parallax(42), parallax(42));
+        assert!(!scenery(&a)[0].is_empty());
+        for &dt in DTS.iter() {
+            a.update(dt);
+            b.update(dt);
+            assert_eq!(scenery(&a), scenery(&b));
+        }
+        assert_ne!(scenery(&parallax(42)), scenery(&parallax(43)));
+    }
+
+    #[test]
+    fn reset_starts_the_same_scenery_over() {
+        let mut a = parallax(7);
+        let start = scenery(&a);
+        for &dt in DTS.iter() {
+            a.update(dt);
+        }
+        a.reset();
+        assert_eq!(a.scroll(), 0.0);
+        assert_eq!(scenery(&a), start);
+        let mut fresh = parallax(7);
+        for &dt in DTS.iter() {
+            a.update(dt);
+            fresh.update(dt);
+            assert_eq!(scenery(&a), scenery(&fresh));
+        }
+    }
+
+    #[test]
+    fn panning_moves_far_layers_slower() {
+        let mut a = parallax(3);
+        a.update(1.0);
+        a.pan_to(200.0);
+        let camera = Camera::new(Vec2::new(200.0, 10.0), 1.0);
+        let offsets: Vec<_> = a
+            .layers()
+            .iter()
+            .map(|layer| a.layer_camera(layer, camera).offset)
+            .collect();
+        assert_eq!(
+            offsets,
+            vec![Vec2::new(115.0, 10.0), Vec2::new(230.0, 10.0)]
+        );
+        // both layers have spawned all the way across the panned view
+        for (layer, offset) in a.layers().iter().zip(offsets.iter()) {
+            assert!(layer.next_x >= offset.x + 100.0);
+            assert!(layer.sprites().iter().any(|s| {
+                let right = s.position.x + s.animation.get_current_frame().w;
+                right >= offset.x && s.position.x < offset.x + 100.0
+            }));
+        }
+    }
+}
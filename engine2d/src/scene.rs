@@ -0,0 +1,190 @@
+use crate::game::Game;
+use crate::input::Input;
+use crate::objects::{Color, Rect};
+use crate::screen::{BlendMode, Screen};
+
+// One screen's worth of game (a title screen, a level, a pause menu...).
+// `Ctx` is whatever the scenes share: resources, audio, scores and so on.
+pub trait Scene<Ctx, ActionID: Ord + Eq> {
+    // Only the top scene gets updated; what it returns says where to go next
+    fn update(&mut self, ctx: &mut Ctx, input: &Input<ActionID>) -> Transition<Ctx, ActionID>;
+
+    fn draw(&self, ctx: &Ctx, screen: &mut Screen, alpha: f32);
+
+    // Overlays get drawn over whatever scene is under them, which stays
+    // frozen (not updated) until the overlay is gone
+    fn is_overlay(&self) -> bool {
+        false
+    }
+}
+
+// Fades out to `color` over `ticks` ticks, makes the switch, and then
+// fades back in over as many again
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Fade {
+    pub color: Color,
+    pub ticks: u32,
+}
+
+impl Fade {
+    pub fn new(color: Color, ticks: u32) -> Self {
+        Self { color, ticks }
+    }
+}
+
+pub enum Transition<Ctx, ActionID: Ord + Eq> {
+    // Stay on this scene
+    None,
+    // Put a new scene on top; this one comes back when it pops
+    Push(Box<dyn Scene<Ctx, ActionID>>),
+    // Go back to the scene underneath
+    Pop,
+    // Swap this scene for a new one
+    Replace(Box<dyn Scene<Ctx, ActionID>>),
+    // Close the game
+    Quit,
+    // Do the inner transition behind a fade; nothing updates meanwhile
+    Fade(Fade, Box<Transition<Ctx, ActionID>>),
+}
+
+impl<Ctx, ActionID: Ord + Eq> Transition<Ctx, ActionID> {
+    pub fn push(scene: impl Scene<Ctx, ActionID> + 'static) -> Self {
+        Transition::Push(Box::new(scene))
+    }
+
+    pub fn replace(scene: impl Scene<Ctx, ActionID> + 'static) -> Self {
+        Transition::Replace(Box::new(scene))
+    }
+
+    pub fn with_fade(self, fade: Fade) -> Self {
+        Transition::Fade(fade, Box::new(self))
+    }
+}
+
+struct Fading<Ctx, ActionID: Ord + Eq> {
+    fade: Fade,
+    tick: u32,
+    // taken once the screen is fully covered
+    pending: Option<Transition<Ctx, ActionID>>,
+}
+
+// Runs the scene on top of the stack, and is itself a Game so it can be
+// handed straight to game::run. Quits once the last scene pops.
+pub struct SceneStack<Ctx, ActionID: Ord + Eq> {
+    ctx: Ctx,
+    scenes: Vec<Box<dyn Scene<Ctx, ActionID>>>,
+    fading: Option<Fading<Ctx, ActionID>>,
+    quit: bool,
+}
+
+impl<Ctx, ActionID: Ord + Eq> SceneStack<Ctx, ActionID> {
+    pub fn new(ctx: Ctx, first: impl Scene<Ctx, ActionID> + 'static) -> Self {
+        Self {
+            ctx,
+            scenes: vec![Box::new(first)],
+            fading: None,
+            quit: false,
+        }
+    }
+
+    pub fn ctx(&self) -> &Ctx {
+        &self.ctx
+    }
+
+    pub fn ctx_mut(&mut self) -> &mut Ctx {
+        &mut self.ctx
+    }
+
+    pub fn len(&self) -> usize {
+        self.scenes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scenes.is_empty()
+    }
+
+    pub fn apply(&mut self, transition: Transition<Ctx, ActionID>) {
+        match transition {
+            Transition::None => {}
+            Transition::Push(scene) => self.scenes.push(scene),
+            Transition::Pop => {
+                self.scenes.pop();
+            }
+            Transition::Replace(scene) => {
+                self.scenes.pop();
+                self.scenes.push(scene);
+            }
+            Transition::Quit => self.quit = true,
+            Transition::Fade(fade, inner) => {
+                if fade.ticks == 0 {
+                    self.apply(*inner);
+                } else {
+                    self.fading = Some(Fading {
+                        fade,
+                        tick: 0,
+                        pending: Some(*inner),
+                    });
+                }
+            }
+        }
+    }
+
+    // How much of the fade color is showing, from 0.0 to 1.0
+    fn fade_amount(&self) -> Option<(Color, f32)> {
+        self.fading.as_ref().map(|f| {
+            let (tick, ticks) = (f.tick as f32, f.fade.ticks as f32);
+            let amount = if tick <= ticks {
+                tick / ticks
+            } else {
+                2.0 - tick / ticks
+            };
+            (f.fade.color, amount)
+        })
+    }
+}
+
+impl<Ctx, ActionID: Ord + Eq> Game for SceneStack<Ctx, ActionID> {
+    type ActionID = ActionID;
+
+    fn update(&mut self, input: &Input<ActionID>) {
+        if let Some(fading) = self.fading.as_mut() {
+            fading.tick += 1;
+            if fading.tick == fading.fade.ticks {
+                if let Some(pending) = fading.pending.take() {
+                    self.apply(pending);
+                }
+            } else if fading.tick >= fading.fade.ticks * 2 {
+                self.fading = None;
+            }
+            return;
+        }
+        if let Some(top) = self.scenes.last_mut() {
+            let transition = top.update(&mut self.ctx, input);
+            self.apply(transition);
+        }
+    }
+
+    fn draw(&self, screen: &mut Screen, alpha: f32) {
+        // Start from the topmost scene that isn't an overlay
+        let bottom = self
+            .scenes
+            .iter()
+            .rposition(|scene| !scene.is_overlay())
+            .unwrap_or(0);
+        for scene in self.scenes[bottom..].iter() {
+            scene.draw(&self.ctx, screen, alpha);
+        }
+        if let Some((mut color, amount)) = self.fade_amount() {
+            color[3] = (color[3] as f32 * amount).round() as u8;
+            let (w, h) = screen.size();
+            let mode = screen.blend_mode();
+            screen.set_blend_mode(BlendMode::Over);
+            screen.screen_space(|s| s.rect(Rect::new(0.0, 0.0, w as f32, h as f32), color));
+            screen.set_blend_mode(mode);
+        }
+    }
+
+    fn running(&self) -> bool {
+        !self.quit && !self.scenes.is_empty()
+    }
+}
@@ -1,12 +1,66 @@
 #![allow(dead_code)]
 // We can pull in definitions from elsewhere in the crate!
+use crate::camera::Camera;
 use crate::objects::{Color, Rect, Vec2};
 use crate::texture::Texture;
+
+// How a drawn color is combined with what's already in the framebuffer.
+// Everything is computed on premultiplied rgba8888, like textures.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum BlendMode {
+    // Overwrite the destination pixel, alpha and all
+    Replace,
+    // Porter-Duff "over", the same compositing bitblt has always done
+    Over,
+    // Add the source onto the destination (glows, flashes)
+    Additive,
+    // Darken the destination by the source (shadows, tinting)
+    Multiply,
+}
+
+// Extra transformations for bitblt_transformed, applied in this order:
+// flip inside the frame, scale, then rotate, both around the pivot.
+#[derive(Copy, Clone, PartialEq)]
+pub struct BlitTransform {
+    pub flip_x: bool,
+    pub flip_y: bool,
+    pub scale: Vec2,
+    // In radians; positive turns clockwise on screen since y points down
+    pub rotation: f32,
+    // Relative to the top-left of the frame, in texture pixels
+    pub pivot: Vec2,
+}
+
+impl Default for BlitTransform {
+    fn default() -> Self {
+        Self {
+            flip_x: false,
+            flip_y: false,
+            scale: Vec2::new(1.0, 1.0),
+            rotation: 0.0,
+            pivot: Vec2::new(0.0, 0.0),
+        }
+    }
+}
+
+impl BlitTransform {
+    // Identity transforms can take the plain (faster) bitblt path
+    pub fn is_identity(&self) -> bool {
+        !self.flip_x
+            && !self.flip_y
+            && self.scale.x == 1.0
+            && self.scale.y == 1.0
+            && self.rotation == 0.0
+    }
+}
+
 pub struct Screen<'fb> {
     framebuffer: &'fb mut [u8],
     width: usize,
     height: usize,
     depth: usize,
+    blend_mode: BlendMode,
+    camera: Camera,
 }
 
 impl<'fb> Screen<'fb> {
@@ -16,6 +70,8 @@ impl<'fb> Screen<'fb> {
             width,
             height,
             depth,
+            blend_mode: BlendMode::Over,
+            camera: Camera::default(),
         }
     }
 
@@ -23,85 +79,113 @@ impl<'fb> Screen<'fb> {
         (self.width, self.height)
     }
 
+    pub fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    // Applies to every draw call after this one, including bitblt
+    pub fn set_blend_mode(&mut self, mode: BlendMode) {
+        self.blend_mode = mode;
+    }
+
+    pub fn camera(&self) -> Camera {
+        self.camera
+    }
+
+    // Every draw call after this one takes world coordinates through `camera`
+    pub fn set_camera(&mut self, camera: Camera) {
+        self.camera = camera;
+    }
+
+    // Draw in raw screen pixels (HUDs, score text) without losing the camera
+    pub fn screen_space<F: FnOnce(&mut Self)>(&mut self, draw: F) {
+        let camera = std::mem::take(&mut self.camera);
+        draw(self);
+        self.camera = camera;
+    }
+
+    // Screen-space area we may draw into: the clip rect, kept on screen
+    fn bounds(&self) -> (f32, f32, f32, f32) {
+        let (w, h) = (self.width as f32, self.height as f32);
+        match self.camera.clip {
+            None => (0.0, 0.0, w, h),
+            Some(c) => (
+                c.x.max(0.0).min(w),
+                c.y.max(0.0).min(h),
+                (c.x + c.w).max(0.0).min(w),
+                (c.y + c.h).max(0.0).min(h),
+            ),
+        }
+    }
+
     // This is not going to be the most efficient API.
     // Lots of bounds checks!
     #[inline(always)]
     pub fn draw_at(&mut self, col: Color, x: usize, y: usize) {
-        // No need to check x or y < 0, they're usizes!
-        if self.width <= x || self.height <= y {
-            return;
-        }
         assert_eq!(self.depth, 4);
-        let idx = y * self.width * self.depth + x * self.depth;
-        // TODO should handle alpha blending!
-        self.framebuffer[idx..(idx + self.depth)].copy_from_slice(&col);
+        let p = self.camera.world_to_screen(Vec2::new(x as f32, y as f32));
+        // zoomed in, a world pixel covers several screen pixels
+        let size = self.camera.zoom.max(1.0);
+        self.fill(p.x, p.y, p.x + size, p.y + size, col);
     }
 
     // If we know the primitives in advance we're in much better shape:
+    // (clearing always replaces the whole screen, whatever the blend mode or camera)
     pub fn clear(&mut self, col: Color) {
         for px in self.framebuffer.chunks_exact_mut(4) {
             px.copy_from_slice(&col);
         }
     }
 
-    pub fn rect_lines(&mut self, r: Rect, col: Color) {
-        let x0 = r.x.max(0.0).min(self.width as f32) as usize;
-        let x1 = (r.x + r.w).max(0.0).min(self.width as f32) as usize;
-        let y0 = r.y.max(0.0).min(self.height as f32) as usize;
-        let y1 = (r.y + r.h).max(0.0).min(self.height as f32) as usize;
+    // Fill the screen-space box from (x0, y0) up to (x1, y1), clipped
+    fn fill(&mut self, x0: f32, y0: f32, x1: f32, y1: f32, col: Color) {
+        let (bx0, by0, bx1, by1) = self.bounds();
+        let x0 = x0.max(bx0).min(bx1) as usize;
+        let x1 = x1.max(bx0).min(bx1) as usize;
+        let y0 = y0.max(by0).min(by1) as usize;
+        let y1 = y1.max(by0).min(by1) as usize;
+        if x1 <= x0 || y1 <= y0 {
+            return;
+        }
         let depth = self.depth;
         let pitch = self.width * depth;
-
-        // vertical lines
-        if r.x > 0.0 {
-            let x0 = x0.min(self.width - 1);
-            for row in y0..y1 {
-                let pixel_idx = row * pitch + x0 * depth;
-                self.framebuffer[pixel_idx..pixel_idx + depth].copy_from_slice(&col);
-            }
-        }
-        if r.x + r.w < self.width as f32 {
-            let x1 = x1.min(self.width - 1);
-            for row in y0..y1 {
-                let pixel_idx = row * pitch + x1 * depth;
-                self.framebuffer[pixel_idx..pixel_idx + depth].copy_from_slice(&col);
+        let mode = self.blend_mode;
+        let col = premultiply(col);
+        for row in self.framebuffer[(y0 * pitch)..(y1 * pitch)].chunks_exact_mut(pitch) {
+            for p in row[(x0 * depth)..(x1 * depth)].chunks_exact_mut(depth) {
+                blend(mode, p, &col);
             }
         }
+    }
 
-        if r.y > 0.0 {
-            let y0 = y0.min(self.height - 1);
-            for p in self.framebuffer[y0 * pitch + x0 * depth..y0 * pitch + x1 * depth]
-                .chunks_exact_mut(depth)
-            {
-                p.copy_from_slice(&col);
-            }
+    // Lines stay one screen pixel thick however far we zoom in
+    pub fn rect_lines(&mut self, r: Rect, col: Color) {
+        let r = self.camera.world_rect_to_screen(r);
+        let (x0, y0) = (r.x, r.y);
+        let (x1, y1) = (r.x + r.w, r.y + r.h);
+
+        // The top and bottom lines take the corners, so translucent colors
+        // don't land twice there; thin rects share their rows and columns.
+        self.fill(x0, y0, x1 + 1.0, y0 + 1.0, col);
+        if y1.floor() > y0.floor() {
+            self.fill(x0, y1, x1 + 1.0, y1 + 1.0, col);
         }
-        if r.y + r.h < self.height as f32 {
-            let y1 = y1.min(self.height - 1);
-            for p in self.framebuffer[y1 * pitch + x0 * depth..y1 * pitch + x1 * depth]
-                .chunks_exact_mut(depth)
-            {
-                p.copy_from_slice(&col);
-            }
+        self.fill(x0, y0 + 1.0, x0 + 1.0, y1, col);
+        if x1.floor() > x0.floor() {
+            self.fill(x1, y0 + 1.0, x1 + 1.0, y1, col);
         }
     }
 
     pub fn rect(&mut self, r: Rect, col: Color) {
-        let x0 = r.x.max(0.0).min(self.width as f32) as usize;
-        let x1 = (r.x + r.w).max(0.0).min(self.width as f32) as usize;
-        let y0 = r.y.max(0.0).min(self.height as f32) as usize;
-        let y1 = (r.y + r.h).max(0.0).min(self.height as f32) as usize;
-        let depth = self.depth;
-        let pitch = self.width * depth;
-        for row in self.framebuffer[(y0 * pitch)..(y1 * pitch)].chunks_exact_mut(pitch) {
-            for p in row[(x0 * depth)..(x1 * depth)].chunks_exact_mut(depth) {
-                // TODO should handle alpha blending
-                p.copy_from_slice(&col);
-            }
-        }
+        let r = self.camera.world_rect_to_screen(r);
+        self.fill(r.x, r.y, r.x + r.w, r.y + r.h, col);
     }
 
-    pub fn line(&mut self, Vec2 { x: x0, y: y0 }: Vec2, Vec2 { x: x1, y: y1 }: Vec2, col: Color) {
+    pub fn line(&mut self, from: Vec2, to: Vec2, col: Color) {
+        // Snap to whole pixels so the stepping below lands exactly on the end
+        let Vec2 { x: x0, y: y0 } = self.camera.world_to_screen(from);
+        let Vec2 { x: x1, y: y1 } = self.camera.world_to_screen(to);
+        let (x0, y0, x1, y1) = (x0.round(), y0.round(), x1.round(), y1.round());
         let mut x = x0;
         let mut y = y0;
         let dx = (x1 - x0).abs();
@@ -109,22 +193,25 @@ impl<'fb> Screen<'fb> {
         let dy = -(y1 - y0).abs();
         let sy = if y0 < y1 { 1.0 } else { -1.0 };
         let mut err = dx + dy;
-        let width = self.width as f32;
-        let height = self.height as f32;
+        let (bx0, by0, bx1, by1) = self.bounds();
         let depth = self.depth;
         let pitch = self.width * depth;
+        let mode = self.blend_mode;
+        let col = premultiply(col);
         #[allow(clippy::all)]
         while x != x1 || y != y1 {
             // We couldn't just clamp x0/y0 and x1/y1 into bounds, because then
             // we might change the slope of the line.
             // We could find the intercept of the line with the left/right or top/bottom edges of the rect though, but that's work!
-            if 0.0 <= x && x < width && 0.0 <= y && y < height {
+            if bx0 <= x && x < bx1 && by0 <= y && y < by1 {
                 // TODO this bounds check could in theory be avoided with
                 // the unsafe get_unchecked, but maybe better not...
-                // TODO better handle alpha blending too, but not just yet...
-                self.framebuffer[(y as usize * pitch + x as usize * depth)
-                    ..(y as usize * pitch + (x as usize + 1) * depth)]
-                    .copy_from_slice(&col);
+                blend(
+                    mode,
+                    &mut self.framebuffer[(y as usize * pitch + x as usize * depth)
+                        ..(y as usize * pitch + (x as usize + 1) * depth)],
+                    &col,
+                );
             }
             let e2 = 2.0 * err;
             if dy <= e2 {
@@ -138,18 +225,48 @@ impl<'fb> Screen<'fb> {
         }
     }
 
-    pub fn bitblt(&mut self, src: &Texture, from: Rect, Vec2 { x: to_x, y: to_y }: Vec2) {
+    pub fn bitblt(&mut self, src: &Texture, from: Rect, to: Vec2) {
+        self.bitblt_transformed(src, from, to, BlitTransform::default());
+    }
+
+    // Like bitblt, but the frame is flipped, scaled and rotated first.
+    // `to` is still where the untransformed frame's top-left would go, so
+    // the pivot stays put at `to + pivot`. Sampling is nearest-neighbor.
+    pub fn bitblt_transformed(
+        &mut self,
+        src: &Texture,
+        from: Rect,
+        to: Vec2,
+        mut xf: BlitTransform,
+    ) {
+        // The camera just moves the pivot and scales everything around it
+        let pivot = self
+            .camera
+            .world_to_screen(Vec2::new(to.x + xf.pivot.x, to.y + xf.pivot.y));
+        let to = Vec2::new(pivot.x - xf.pivot.x, pivot.y - xf.pivot.y);
+        xf.scale = Vec2::new(xf.scale.x * self.camera.zoom, xf.scale.y * self.camera.zoom);
+        if xf.is_identity() {
+            self.blit_at(src, from, to);
+        } else {
+            self.blit_transformed_at(src, from, to, xf);
+        }
+    }
+
+    // The 1:1 blit, `to` already in screen space
+    fn blit_at(&mut self, src: &Texture, from: Rect, Vec2 { x: to_x, y: to_y }: Vec2) {
         let (tw, th) = src.size();
         assert!(0.0 <= from.x);
         assert!(from.x < tw as f32);
         assert!(0.0 <= from.y);
         assert!(from.y < th as f32);
+        let (bx0, by0, bx1, by1) = self.bounds();
+        let (bx0, by0, bx1, by1) = (bx0 as i32, by0 as i32, bx1 as i32, by1 as i32);
         let to_x = to_x as i32;
         let to_y = to_y as i32;
-        if (to_x + from.w as i32) < 0
-            || (self.width as i32) <= to_x
-            || (to_y + from.h as i32) < 0
-            || (self.height as i32) <= to_y
+        if (to_x + from.w as i32) < bx0
+            || bx1 <= to_x
+            || (to_y + from.h as i32) < by0
+            || by1 <= to_y
         {
             return;
         }
@@ -160,10 +277,10 @@ impl<'fb> Screen<'fb> {
         // All this rigmarole is just to avoid bounds checks on each pixel of the blit.
         // We want to calculate which row/col of the src image to start at and which to end at.
         // This way there's no need to even check for out of bounds draws.
-        let y_skip = to_y.max(0) - to_y;
-        let x_skip = to_x.max(0) - to_x;
-        let y_count = (to_y + from.h as i32).min(self.height as i32) - to_y;
-        let x_count = (to_x + from.w as i32).min(self.width as i32) - to_x;
+        let y_skip = to_y.max(by0) - to_y;
+        let x_skip = to_x.max(bx0) - to_x;
+        let y_count = (to_y + from.h as i32).min(by1) - to_y;
+        let x_count = (to_x + from.w as i32).min(bx1) - to_x;
         let src_buf = src.buffer();
         for (row_a, row_b) in src_buf[(src_pitch * ((from.y as i32 + y_skip) as usize))
             ..(src_pitch * ((from.y as i32 + y_count) as usize))]
@@ -180,30 +297,167 @@ impl<'fb> Screen<'fb> {
             let from_cols = row_a[(depth * (from.x as i32 + x_skip) as usize)
                 ..(depth * (from.x as i32 + x_count) as usize)]
                 .chunks_exact(depth);
-            // Composite over, assume premultiplied rgba8888
+            // Assume premultiplied rgba8888
             for (to, from) in to_cols.zip(from_cols) {
-                let ta = to[3] as f32 / 255.0;
-                let fa = from[3] as f32 / 255.0;
-                for i in 0..3 {
-                    to[i] = from[i].saturating_add((to[i] as f32 * (1.0 - fa)).round() as u8);
+                blend(self.blend_mode, to, from);
+            }
+        }
+    }
+
+    // The transformed blit, `to` already in screen space
+    fn blit_transformed_at(&mut self, src: &Texture, from: Rect, to: Vec2, xf: BlitTransform) {
+        if xf.scale.x == 0.0 || xf.scale.y == 0.0 {
+            return;
+        }
+        let depth = self.depth;
+        assert_eq!(depth, src.depth());
+        assert!(src.valid_frame(from));
+        let (sin, cos) = xf.rotation.sin_cos();
+        let origin = Vec2::new(to.x + xf.pivot.x, to.y + xf.pivot.y);
+
+        // Find where the corners of the frame land to get the area to fill
+        let corners = [(0.0, 0.0), (from.w, 0.0), (0.0, from.h), (from.w, from.h)];
+        let (mut min_x, mut min_y) = (f32::MAX, f32::MAX);
+        let (mut max_x, mut max_y) = (f32::MIN, f32::MIN);
+        for (cx, cy) in corners.iter() {
+            let sx = (cx - xf.pivot.x) * xf.scale.x;
+            let sy = (cy - xf.pivot.y) * xf.scale.y;
+            let dx = origin.x + sx * cos - sy * sin;
+            let dy = origin.y + sx * sin + sy * cos;
+            min_x = min_x.min(dx);
+            min_y = min_y.min(dy);
+            max_x = max_x.max(dx);
+            max_y = max_y.max(dy);
+        }
+        let (bx0, by0, bx1, by1) = self.bounds();
+        let x0 = min_x.floor().max(bx0).min(bx1) as usize;
+        let y0 = min_y.floor().max(by0).min(by1) as usize;
+        let x1 = max_x.ceil().max(bx0).min(bx1) as usize;
+        let y1 = max_y.ceil().max(by0).min(by1) as usize;
+
+        let mode = self.blend_mode;
+        let src_pitch = src.pitch();
+        let dst_pitch = self.width * depth;
+        let src_buf = src.buffer();
+        for y in y0..y1 {
+            for x in x0..x1 {
+                // Map the center of this screen pixel back into the frame
+                let dx = x as f32 + 0.5 - origin.x;
+                let dy = y as f32 + 0.5 - origin.y;
+                let mut u = (dx * cos + dy * sin) / xf.scale.x + xf.pivot.x;
+                let mut v = (-dx * sin + dy * cos) / xf.scale.y + xf.pivot.y;
+                if !(0.0..from.w).contains(&u) || !(0.0..from.h).contains(&v) {
+                    continue;
+                }
+                if xf.flip_x {
+                    u = from.w - u;
                 }
-                to[3] = ((fa + ta * (1.0 - fa)) * 255.0).round() as u8;
+                if xf.flip_y {
+                    v = from.h - v;
+                }
+                let u = ((from.x + u) as usize).min((from.x + from.w) as usize - 1);
+                let v = ((from.y + v) as usize).min((from.y + from.h) as usize - 1);
+                let src_idx = v * src_pitch + u * depth;
+                let dst_idx = y * dst_pitch + x * depth;
+                blend(
+                    mode,
+                    &mut self.framebuffer[dst_idx..dst_idx + depth],
+                    &src_buf[src_idx..src_idx + depth],
+                );
             }
         }
     }
 
     pub fn filled_circle(&mut self, (x, y): (i32, i32), r: u64, col: Color) {
+        let center = self.camera.world_to_screen(Vec2::new(x as f32, y as f32));
+        let (x, y) = (center.x.round() as i32, center.y.round() as i32);
+        let r = (r as f32 * self.camera.zoom).round() as u64;
+        let (bx0, by0, bx1, by1) = self.bounds();
+        let (bx0, by0, bx1, by1) = (bx0 as i32, by0 as i32, bx1 as i32, by1 as i32);
+        let mode = self.blend_mode;
+        let col = premultiply(col);
         for i in x - r as i32..x + r as i32 {
             for j in y - r as i32..y + r as i32 {
                 if crate::objects::dist((i, j), (x, y)) < r as f32
-                    && (x >= 0 && x < self.width as i32)
-                    && (y >= 0 && y < self.height as i32)
+                    && (i >= bx0 && i < bx1)
+                    && (j >= by0 && j < by1)
                 {
-                    self.framebuffer[self.width * self.depth * j as usize + i as usize * self.depth
-                        ..self.width * self.depth * j as usize + (i + 1) as usize * self.depth]
-                        .copy_from_slice(&col);
+                    blend(
+                        mode,
+                        &mut self.framebuffer[self.width * self.depth * j as usize
+                            + i as usize * self.depth
+                            ..self.width * self.depth * j as usize + (i + 1) as usize * self.depth],
+                        &col,
+                    );
                 }
             }
         }
     }
 }
+
+// Colors are given straight (not premultiplied), but the framebuffer and
+// textures are premultiplied, so convert before blending.
+#[inline(always)]
+fn premultiply(col: Color) -> Color {
+    let a = col[3] as f32 / 255.0;
+    [
+        (col[0] as f32 * a).round() as u8,
+        (col[1] as f32 * a).round() as u8,
+        (col[2] as f32 * a).round() as u8,
+        col[3],
+    ]
+}
+
+// Combine one premultiplied rgba8888 pixel `from` into `to`.
+#[inline(always)]
+fn blend(mode: BlendMode, to: &mut [u8], from: &[u8]) {
+    let ta = to[3] as f32 / 255.0;
+    let fa = from[3] as f32 / 255.0;
+    match mode {
+        BlendMode::Replace => to.copy_from_slice(from),
+        BlendMode::Over => {
+            for i in 0..3 {
+                to[i] = from[i].saturating_add((to[i] as f32 * (1.0 - fa)).round() as u8);
+            }
+            to[3] = ((fa + ta * (1.0 - fa)) * 255.0).round() as u8;
+        }
+        BlendMode::Additive => {
+            for i in 0..4 {
+                to[i] = to[i].saturating_add(from[i]);
+            }
+        }
+        BlendMode::Multiply => {
+            // src*dst plus whatever of each isn't covered by the other
+            for i in 0..3 {
+                let f = from[i] as f32;
+                let t = to[i] as f32;
+                to[i] = (f * t / 255.0 + f * (1.0 - ta) + t * (1.0 - fa))
+                    .round()
+                    .min(255.0) as u8;
+            }
+            to[3] = ((fa + ta * (1.0 - fa)) * 255.0).round() as u8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rect_lines_blend_every_pixel_once() {
+        let mut fb = vec![0; 8 * 8 * 4];
+        let mut screen = Screen::wrap(&mut fb, 8, 8, 4);
+        screen.set_blend_mode(BlendMode::Additive);
+        screen.rect_lines(Rect::new(1.0, 2.0, 4.0, 3.0), [10, 10, 10, 255]);
+        let outline = |x: usize, y: usize| {
+            (x == 1 || x == 5) && (2..=5).contains(&y) || (y == 2 || y == 5) && (1..=5).contains(&x)
+        };
+        for y in 0..8 {
+            for x in 0..8 {
+                let want = if outline(x, y) { 10 } else { 0 };
+                assert_eq!(fb[(y * 8 + x) * 4], want, "pixel ({}, {})", x, y);
+            }
+        }
+    }
+}
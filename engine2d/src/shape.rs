@@ -0,0 +1,313 @@
+use crate::objects::{Color, Rect, Vec2};
+use crate::screen::Screen;
+
+// Colliders that aren't axis-aligned boxes. MTVs follow the same rule as
+// collision::rect_displacement: moving the first shape by the vector pushes
+// it out of the second one, and shapes that only touch don't overlap.
+
+#[derive(Copy, Clone, PartialEq)]
+pub struct Circle {
+    pub center: Vec2,
+    pub radius: f32,
+}
+
+impl Circle {
+    pub fn new(center: Vec2, radius: f32) -> Self {
+        Self { center, radius }
+    }
+
+    pub fn bounds(&self) -> Rect {
+        Rect::new(
+            self.center.x - self.radius,
+            self.center.y - self.radius,
+            self.radius * 2.0,
+            self.radius * 2.0,
+        )
+    }
+}
+
+// A convex polygon; the points can go either way around, but anything
+// concave will give wrong answers.
+#[derive(Clone, PartialEq)]
+pub struct Polygon {
+    points: Vec<Vec2>,
+}
+
+impl Polygon {
+    pub fn new(points: Vec<Vec2>) -> Self {
+        assert!(points.len() >= 3, "a polygon needs at least 3 points");
+        Self { points }
+    }
+
+    pub fn from_rect(r: Rect) -> Self {
+        Self::new(vec![
+            Vec2::new(r.x, r.y),
+            Vec2::new(r.x + r.w, r.y),
+            Vec2::new(r.x + r.w, r.y + r.h),
+            Vec2::new(r.x, r.y + r.h),
+        ])
+    }
+
+    pub fn points(&self) -> &[Vec2] {
+        &self.points
+    }
+
+    pub fn translate(&mut self, by: Vec2) {
+        for p in self.points.iter_mut() {
+            *p += by;
+        }
+    }
+
+    // Average of the corners
+    pub fn center(&self) -> Vec2 {
+        let sum = self.points.iter().fold(Vec2::zero(), |sum, p| sum + *p);
+        sum / self.points.len() as f32
+    }
+
+    pub fn bounds(&self) -> Rect {
+        let first = self.points[0];
+        let (x0, y0, x1, y1) = self.points.iter().fold(
+            (first.x, first.y, first.x, first.y),
+            |(x0, y0, x1, y1), p| (x0.min(p.x), y0.min(p.y), x1.max(p.x), y1.max(p.y)),
+        );
+        Rect::new(x0, y0, x1 - x0, y1 - y0)
+    }
+
+    // The unit normal of every edge; these are the axes SAT has to try
+    fn axes(&self) -> impl Iterator<Item = Vec2> + '_ {
+        let n = self.points.len();
+        (0..n).filter_map(move |i| {
+            let (a, b) = (self.points[i], self.points[(i + 1) % n]);
+            (b - a).perp().try_normalize()
+        })
+    }
+
+    fn project(&self, axis: Vec2) -> (f32, f32) {
+        self.points
+            .iter()
+            .map(|p| p.dot(axis))
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), d| {
+                (lo.min(d), hi.max(d))
+            })
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub enum Shape {
+    Rect(Rect),
+    Circle(Circle),
+    Polygon(Polygon),
+}
+
+impl Shape {
+    pub fn bounds(&self) -> Rect {
+        match self {
+            Shape::Rect(r) => *r,
+            Shape::Circle(c) => c.bounds(),
+            Shape::Polygon(p) => p.bounds(),
+        }
+    }
+
+    pub fn translate(&mut self, by: Vec2) {
+        match self {
+            Shape::Rect(r) => *r = r.translate(by),
+            Shape::Circle(c) => c.center += by,
+            Shape::Polygon(p) => p.translate(by),
+        }
+    }
+}
+
+// The shorter way to move projection `a` along an axis so it stops
+// overlapping `b`: negative is back along the axis. None if they don't
+// overlap. Going by the ends rather than the middles means a shape sitting
+// inside another still gets pushed all the way out.
+fn separation((lo1, hi1): (f32, f32), (lo2, hi2): (f32, f32)) -> Option<f32> {
+    let (back, forward) = (hi1 - lo2, hi2 - lo1);
+    if back <= 0.0 || forward <= 0.0 {
+        None
+    } else if back < forward {
+        Some(-back)
+    } else {
+        Some(forward)
+    }
+}
+
+pub fn circle_circle(a: &Circle, b: &Circle) -> Option<Vec2> {
+    let d = a.center - b.center;
+    let depth = a.radius + b.radius - d.length();
+    if depth <= 0.0 {
+        return None;
+    }
+    // dead center: any way out is as good as the others, so go up
+    let dir = d.try_normalize().unwrap_or_else(|| Vec2::new(0.0, -1.0));
+    Some(dir * depth)
+}
+
+// Separating axis theorem: two convex shapes overlap unless some edge normal
+// has a gap between their shadows. If none does, the normal needing the
+// shortest move is the cheapest way apart.
+pub fn polygon_polygon(a: &Polygon, b: &Polygon) -> Option<Vec2> {
+    let mut best: Option<Vec2> = None;
+    for axis in a.axes().chain(b.axes()) {
+        let mtv = axis * separation(a.project(axis), b.project(axis))?;
+        if best.is_none_or(|best| mtv.length_squared() < best.length_squared()) {
+            best = Some(mtv);
+        }
+    }
+    best
+}
+
+// Same idea, but a circle has no edges of its own; the only extra axis it
+// needs is the one towards the polygon's nearest corner.
+pub fn circle_polygon(a: &Circle, b: &Polygon) -> Option<Vec2> {
+    let nearest = b.points.iter().copied().min_by(|p, q| {
+        (*p - a.center)
+            .length_squared()
+            .partial_cmp(&(*q - a.center).length_squared())
+            .unwrap_or(std::cmp::Ordering::Equal)
+    })?;
+    let corner_axis = (nearest - a.center).try_normalize();
+    let mut best: Option<Vec2> = None;
+    for axis in b.axes().chain(corner_axis) {
+        let c = a.center.dot(axis);
+        let mtv = axis * separation((c - a.radius, c + a.radius), b.project(axis))?;
+        if best.is_none_or(|best| mtv.length_squared() < best.length_squared()) {
+            best = Some(mtv);
+        }
+    }
+    best
+}
+
+// The MTV for any pair of shapes; rects get treated as 4-sided polygons
+pub fn shape_displacement(a: &Shape, b: &Shape) -> Option<Vec2> {
+    match (a, b) {
+        (Shape::Rect(a), Shape::Rect(b)) => crate::collision::rect_displacement(*a, *b),
+        (Shape::Circle(a), Shape::Circle(b)) => circle_circle(a, b),
+        (Shape::Circle(a), Shape::Polygon(b)) => circle_polygon(a, b),
+        (Shape::Circle(a), Shape::Rect(b)) => circle_polygon(a, &Polygon::from_rect(*b)),
+        (Shape::Polygon(a), Shape::Circle(b)) => circle_polygon(b, a).map(|v| -v),
+        (Shape::Rect(a), Shape::Circle(b)) => {
+            circle_polygon(b, &Polygon::from_rect(*a)).map(|v| -v)
+        }
+        (Shape::Polygon(a), Shape::Polygon(b)) => polygon_polygon(a, b),
+        (Shape::Polygon(a), Shape::Rect(b)) => polygon_polygon(a, &Polygon::from_rect(*b)),
+        (Shape::Rect(a), Shape::Polygon(b)) => polygon_polygon(&Polygon::from_rect(*a), b),
+    }
+}
+
+pub fn shapes_overlap(a: &Shape, b: &Shape) -> bool {
+    shape_displacement(a, b).is_some()
+}
+
+pub trait DrawShapeExt {
+    fn draw_shape(&mut self, s: &Shape, col: Color);
+}
+
+impl<'fb> DrawShapeExt for Screen<'fb> {
+    // Outlines for rects and polygons, a filled disc for circles; use a
+    // see-through color to keep what's underneath visible
+    fn draw_shape(&mut self, s: &Shape, col: Color) {
+        match s {
+            Shape::Rect(r) => self.rect_lines(*r, col),
+            Shape::Circle(c) => self.filled_circle(
+                (c.center.x.round() as i32, c.center.y.round() as i32),
+                c.radius.round() as u64,
+                col,
+            ),
+            Shape::Polygon(p) => {
+                let n = p.points.len();
+                for i in 0..n {
+                    self.line(p.points[i], p.points[(i + 1) % n], col);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(x: f32, y: f32, size: f32) -> Polygon {
+        Polygon::from_rect(Rect::new(x, y, size, size))
+    }
+
+    fn close(a: Vec2, b: Vec2) -> bool {
+        a.distance(b) < 1e-4
+    }
+
+    #[test]
+    fn circle_against_an_edge() {
+        let b = square(0.0, 0.0, 10.0);
+        let mtv = circle_polygon(&Circle::new(Vec2::new(5.0, -2.0), 3.0), &b).unwrap();
+        assert!(close(mtv, Vec2::new(0.0, -1.0)), "{:?}", mtv);
+        let mtv = circle_polygon(&Circle::new(Vec2::new(11.0, 4.0), 3.0), &b).unwrap();
+        assert!(close(mtv, Vec2::new(2.0, 0.0)), "{:?}", mtv);
+        assert_eq!(
+            circle_polygon(&Circle::new(Vec2::new(5.0, -3.0), 3.0), &b),
+            None
+        );
+    }
+
+    #[test]
+    fn circle_against_a_corner() {
+        let b = square(0.0, 0.0, 10.0);
+        // inside both edges' slabs but short of the corner itself
+        assert_eq!(
+            circle_polygon(&Circle::new(Vec2::new(-2.5, -2.5), 3.0), &b),
+            None
+        );
+        let mtv = circle_polygon(&Circle::new(Vec2::new(-2.0, -2.0), 3.0), &b).unwrap();
+        let depth = 3.0 - 8.0f32.sqrt();
+        assert!(
+            close(mtv, Vec2::new(-1.0, -1.0).normalize() * depth),
+            "{:?}",
+            mtv
+        );
+    }
+
+    #[test]
+    fn touching_polygons_dont_overlap() {
+        let a = square(0.0, 0.0, 10.0);
+        assert_eq!(polygon_polygon(&a, &square(10.0, 3.0, 10.0)), None);
+        assert_eq!(polygon_polygon(&a, &square(10.0, 10.0, 10.0)), None);
+        // a diamond with one point on a's right edge
+        let diamond = Polygon::new(vec![
+            Vec2::new(10.0, 5.0),
+            Vec2::new(15.0, 0.0),
+            Vec2::new(20.0, 5.0),
+            Vec2::new(15.0, 10.0),
+        ]);
+        assert_eq!(polygon_polygon(&a, &diamond), None);
+    }
+
+    #[test]
+    fn overlapping_polygons_push_apart() {
+        let a = square(0.0, 0.0, 10.0);
+        let b = square(8.0, 2.0, 10.0);
+        let mtv = polygon_polygon(&a, &b).unwrap();
+        assert!(close(mtv, Vec2::new(-2.0, 0.0)), "{:?}", mtv);
+        let back = polygon_polygon(&b, &a).unwrap();
+        assert!(close(back, Vec2::new(2.0, 0.0)), "{:?}", back);
+        // moved by the MTV, they only touch
+        let mut moved = a.clone();
+        moved.translate(mtv);
+        assert_eq!(polygon_polygon(&moved, &b), None);
+    }
+
+    #[test]
+    fn contained_shapes_get_pushed_all_the_way_out() {
+        let big = square(0.0, 0.0, 10.0);
+        let small = square(1.0, 4.0, 2.0);
+        let mtv = polygon_polygon(&small, &big).unwrap();
+        assert!(close(mtv, Vec2::new(-3.0, 0.0)), "{:?}", mtv);
+        let out = polygon_polygon(&big, &small).unwrap();
+        assert!(close(out, Vec2::new(3.0, 0.0)), "{:?}", out);
+
+        let circle = Circle::new(Vec2::new(5.0, 8.0), 1.0);
+        let mtv = circle_polygon(&circle, &big).unwrap();
+        assert!(close(mtv, Vec2::new(0.0, 3.0)), "{:?}", mtv);
+        let moved = Circle::new(circle.center + mtv, circle.radius);
+        assert_eq!(circle_polygon(&moved, &big), None);
+    }
+}
@@ -1,12 +1,16 @@
 use crate::animation::{Animation, AnimationData};
 use crate::objects::Vec2;
+use crate::screen::BlitTransform;
 use crate::texture::Texture;
+use crate::transform::Transform2D;
 use std::rc::Rc;
 
 pub struct Sprite {
     image: Rc<Texture>,
     pub animation: Animation,
     pub position: Vec2,
+    // Flip/scale/rotation applied when drawing, around transform.pivot
+    pub transform: BlitTransform,
 }
 
 impl Sprite {
@@ -15,6 +19,7 @@ impl Sprite {
             image: Rc::clone(image),
             animation,
             position,
+            transform: BlitTransform::default(),
         }
     }
 
@@ -23,8 +28,202 @@ impl Sprite {
     }
 }
 
+// A sprite in a SpriteGraph. Ids of removed nodes stay dead, even once
+// their slot gets reused.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct NodeId {
+    index: usize,
+    generation: u32,
+}
+
+struct Node {
+    sprite: Sprite,
+    local: Transform2D,
+    world: Transform2D,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+}
+
+// Sprites placed relative to each other: each node has a transform local to
+// its parent, and update() works out where everything ends up. A node's
+// world transform puts its sprite's pivot at the translation, rotated and
+// scaled around it; update() writes that into the sprite's position,
+// rotation and scale, so the sprite can still be drawn or tested on its own.
+pub struct SpriteGraph {
+    // generation of each slot, and the node in it (None when free)
+    nodes: Vec<(u32, Option<Node>)>,
+    free: Vec<usize>,
+    roots: Vec<NodeId>,
+}
+
+impl Default for SpriteGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SpriteGraph {
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![],
+            free: vec![],
+            roots: vec![],
+        }
+    }
+
+    pub fn add(&mut self, sprite: Sprite, local: Transform2D, parent: Option<NodeId>) -> NodeId {
+        let node = Node {
+            sprite,
+            local,
+            world: local,
+            parent,
+            children: vec![],
+        };
+        let id = match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.nodes[index];
+                slot.0 += 1;
+                slot.1 = Some(node);
+                NodeId {
+                    index,
+                    generation: slot.0,
+                }
+            }
+            None => {
+                self.nodes.push((0, Some(node)));
+                NodeId {
+                    index: self.nodes.len() - 1,
+                    generation: 0,
+                }
+            }
+        };
+        self.link(id, parent);
+        id
+    }
+
+    // Takes out the node and everything under it
+    pub fn remove(&mut self, id: NodeId) {
+        if !self.contains(id) {
+            return;
+        }
+        self.unlink(id);
+        let mut doomed = vec![id];
+        while let Some(id) = doomed.pop() {
+            if let Some(node) = self.nodes[id.index].1.take() {
+                doomed.extend(node.children);
+                self.free.push(id.index);
+            }
+        }
+    }
+
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.nodes
+            .get(id.index)
+            .is_some_and(|(generation, node)| *generation == id.generation && node.is_some())
+    }
+
+    fn node(&self, id: NodeId) -> &Node {
+        assert!(self.contains(id), "node was removed");
+        self.nodes[id.index].1.as_ref().unwrap()
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        assert!(self.contains(id), "node was removed");
+        self.nodes[id.index].1.as_mut().unwrap()
+    }
+
+    fn link(&mut self, id: NodeId, parent: Option<NodeId>) {
+        self.node_mut(id).parent = parent;
+        match parent {
+            Some(parent) => self.node_mut(parent).children.push(id),
+            None => self.roots.push(id),
+        }
+    }
+
+    fn unlink(&mut self, id: NodeId) {
+        let siblings = match self.node(id).parent {
+            Some(parent) => &mut self.node_mut(parent).children,
+            None => &mut self.roots,
+        };
+        siblings.retain(|&other| other != id);
+    }
+
+    // Moves a node (and what's under it) to a new parent, keeping its local
+    // transform. Can't be moved under itself.
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) {
+        let mut up = parent;
+        while let Some(ancestor) = up {
+            assert!(ancestor != id, "a node can't be its own ancestor");
+            up = self.node(ancestor).parent;
+        }
+        self.unlink(id);
+        self.link(id, parent);
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.node(id).parent
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        &self.node(id).children
+    }
+
+    pub fn sprite(&self, id: NodeId) -> &Sprite {
+        &self.node(id).sprite
+    }
+
+    pub fn sprite_mut(&mut self, id: NodeId) -> &mut Sprite {
+        &mut self.node_mut(id).sprite
+    }
+
+    pub fn local(&self, id: NodeId) -> &Transform2D {
+        &self.node(id).local
+    }
+
+    pub fn local_mut(&mut self, id: NodeId) -> &mut Transform2D {
+        &mut self.node_mut(id).local
+    }
+
+    // As of the last update()
+    pub fn world(&self, id: NodeId) -> &Transform2D {
+        &self.node(id).world
+    }
+
+    // Recomputes every world transform, parents before children
+    pub fn update(&mut self) {
+        let mut todo: Vec<(NodeId, Transform2D)> = self
+            .roots
+            .iter()
+            .rev()
+            .map(|&id| (id, Transform2D::identity()))
+            .collect();
+        while let Some((id, parent_world)) = todo.pop() {
+            let node = self.node_mut(id);
+            node.world = parent_world.compose(&node.local);
+            let world = node.world;
+            let sprite = &mut node.sprite;
+            sprite.position = world.translation - sprite.transform.pivot;
+            sprite.transform.rotation = world.rotation;
+            sprite.transform.scale = world.scale;
+            todo.extend(node.children.iter().rev().map(|&child| (child, world)));
+        }
+    }
+
+    // Parents come before their children, so children get drawn on top
+    pub fn iter(&self) -> impl Iterator<Item = &Sprite> + '_ {
+        let mut todo: Vec<NodeId> = self.roots.iter().rev().copied().collect();
+        std::iter::from_fn(move || {
+            let id = todo.pop()?;
+            let node = self.node(id);
+            todo.extend(node.children.iter().rev());
+            Some(&node.sprite)
+        })
+    }
+}
+
 pub trait DrawSpriteExt {
     fn draw_sprite(&mut self, s: &Sprite);
+    fn draw_sprite_graph(&mut self, g: &SpriteGraph);
 }
 
 use crate::screen::Screen;
@@ -32,6 +231,96 @@ impl<'fb> DrawSpriteExt for Screen<'fb> {
     fn draw_sprite(&mut self, s: &Sprite) {
         // This works because we're only using a public method of Screen here,
         // and the private fields of sprite are visible inside this module
-        self.bitblt(&s.image, s.animation.get_current_frame(), s.position);
+        self.bitblt_transformed(
+            &s.image,
+            s.animation.get_current_frame(),
+            s.position,
+            s.transform,
+        );
+    }
+
+    fn draw_sprite_graph(&mut self, g: &SpriteGraph) {
+        for s in g.iter() {
+            self.draw_sprite(s);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animation::Playback;
+    use crate::objects::Rect;
+    use std::f32::consts::FRAC_PI_2;
+
+    // A 4x4 blank sprite pivoting on its center
+    fn sprite() -> Sprite {
+        let texture = Rc::new(Texture::from_premultiplied(vec![0; 4 * 4 * 4], 4, 4));
+        let frames = vec![(Rect::new(0.0, 0.0, 4.0, 4.0), 1.0)];
+        let animation = Animation::new(&Rc::new(AnimationData::new(frames, Playback::Once)));
+        let mut sprite = Sprite::new(&texture, animation, Vec2::zero());
+        sprite.transform.pivot = Vec2::new(2.0, 2.0);
+        sprite
+    }
+
+    fn at(x: f32, y: f32) -> Transform2D {
+        Transform2D::from_translation(Vec2::new(x, y))
+    }
+
+    #[test]
+    fn children_follow_their_parents() {
+        let mut graph = SpriteGraph::new();
+        let body = graph.add(sprite(), at(10.0, 10.0), None);
+        let arm = graph.add(sprite(), at(5.0, 0.0), Some(body));
+        graph.update();
+        assert_eq!(graph.world(arm).translation, Vec2::new(15.0, 10.0));
+        assert_eq!(graph.sprite(arm).position, Vec2::new(13.0, 8.0));
+
+        let local = graph.local_mut(body);
+        local.translation = Vec2::new(20.0, 0.0);
+        local.rotation = FRAC_PI_2;
+        graph.update();
+        let world = graph.world(arm);
+        assert!(world.translation.distance(Vec2::new(20.0, 5.0)) < 1e-4);
+        assert_eq!(world.rotation, FRAC_PI_2);
+        assert_eq!(graph.sprite(arm).transform.rotation, FRAC_PI_2);
+    }
+
+    #[test]
+    fn removing_a_node_removes_what_hangs_off_it() {
+        let mut graph = SpriteGraph::new();
+        let body = graph.add(sprite(), at(0.0, 0.0), None);
+        let arm = graph.add(sprite(), at(5.0, 0.0), Some(body));
+        let hand = graph.add(sprite(), at(5.0, 0.0), Some(arm));
+        let other = graph.add(sprite(), at(0.0, 0.0), None);
+        graph.remove(arm);
+        assert!(!graph.contains(arm) && !graph.contains(hand));
+        assert!(graph.contains(body) && graph.contains(other));
+        assert!(graph.children(body).is_empty());
+        assert_eq!(graph.iter().count(), 2);
+    }
+
+    #[test]
+    fn stale_ids_stay_dead_when_their_slot_is_reused() {
+        let mut graph = SpriteGraph::new();
+        let old = graph.add(sprite(), at(0.0, 0.0), None);
+        graph.remove(old);
+        let new = graph.add(sprite(), at(1.0, 1.0), None);
+        assert_eq!(new.index, old.index);
+        assert!(!graph.contains(old) && graph.contains(new));
+        // removing by the stale id leaves the new node alone
+        graph.remove(old);
+        assert!(graph.contains(new));
+        assert_eq!(graph.iter().count(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "node was removed")]
+    fn stale_ids_cant_reach_the_new_node() {
+        let mut graph = SpriteGraph::new();
+        let old = graph.add(sprite(), at(0.0, 0.0), None);
+        graph.remove(old);
+        graph.add(sprite(), at(0.0, 0.0), None);
+        graph.sprite(old);
     }
 }
@@ -0,0 +1,475 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+use serde::Deserialize;
+
+use crate::animation::{AnimationData, Playback};
+use crate::objects::{Rect, Vec2};
+use crate::texture::Texture;
+
+// How long frames show when the file doesn't say (TexturePacker never does)
+const DEFAULT_FRAME_TIME: f32 = 0.1;
+
+#[derive(Debug)]
+pub enum SheetError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Image(image::error::ImageError),
+    // The file parsed but isn't a sheet we can use
+    Format(String),
+}
+
+impl fmt::Display for SheetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "couldn't read sprite sheet: {}", e),
+            Self::Json(e) => write!(f, "bad sprite sheet json: {}", e),
+            Self::Image(e) => write!(f, "couldn't load sprite sheet image: {}", e),
+            Self::Format(e) => write!(f, "unsupported sprite sheet: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SheetError {}
+
+impl From<std::io::Error> for SheetError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SheetError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Json(e)
+    }
+}
+
+impl From<image::error::ImageError> for SheetError {
+    fn from(e: image::error::ImageError) -> Self {
+        Self::Image(e)
+    }
+}
+
+fn format_err<T>(msg: impl Into<String>) -> Result<T, SheetError> {
+    Err(SheetError::Format(msg.into()))
+}
+
+pub struct SheetFrame {
+    pub name: String,
+    // Where the frame is in the texture
+    pub rect: Rect,
+    // Seconds
+    pub duration: f32,
+    // Packers trim see-through edges off; this is where the trimmed frame
+    // goes inside the original, untrimmed one. Sprites don't use it.
+    pub offset: Vec2,
+    // In pixels from the untrimmed frame's top-left, if the file has one
+    pub pivot: Option<Vec2>,
+}
+
+// A named area marked out in Aseprite
+pub struct Slice {
+    pub name: String,
+    pub bounds: Rect,
+    // The middle part of a 9-slice, relative to bounds
+    pub center: Option<Rect>,
+    // Relative to bounds
+    pub pivot: Option<Vec2>,
+}
+
+// A texture and the named frames, clips and slices cut out of it. Loads
+// the JSON that Aseprite (File > Export Sprite Sheet) and TexturePacker
+// write, in both their hash and array flavours.
+pub struct SpriteSheet {
+    pub texture: Rc<Texture>,
+    pub frames: Vec<SheetFrame>,
+    // One per Aseprite tag or TexturePacker animation, plus a one-frame
+    // clip for every frame named after it (tags win when names clash)
+    pub clips: BTreeMap<String, Rc<AnimationData>>,
+    pub slices: Vec<Slice>,
+}
+
+impl SpriteSheet {
+    pub fn load(path: &Path) -> Result<Self, SheetError> {
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        Self::from_json(&fs::read_to_string(path)?, dir)
+    }
+
+    // `dir` is where the image path in the file is relative to
+    pub fn from_json(text: &str, dir: &Path) -> Result<Self, SheetError> {
+        let raw: json::Sheet = serde_json::from_str(text)?;
+        json::convert(raw, dir)
+    }
+
+    pub fn clip(&self, name: &str) -> Option<&Rc<AnimationData>> {
+        self.clips.get(name)
+    }
+
+    pub fn frame(&self, name: &str) -> Option<&SheetFrame> {
+        self.frames.iter().find(|f| f.name == name)
+    }
+
+    pub fn slice(&self, name: &str) -> Option<&Slice> {
+        self.slices.iter().find(|s| s.name == name)
+    }
+}
+
+mod json {
+    use super::*;
+    use serde::de::{Deserializer, MapAccess, SeqAccess, Visitor};
+
+    #[derive(Deserialize)]
+    pub struct Sheet {
+        #[serde(deserialize_with = "frames")]
+        frames: Vec<(String, FrameData)>,
+        meta: Meta,
+        // TexturePacker's Pixi/Phaser exports list frame names per animation
+        #[serde(default)]
+        animations: BTreeMap<String, Vec<String>>,
+    }
+
+    #[derive(Deserialize)]
+    struct FrameData {
+        filename: Option<String>,
+        frame: RectData,
+        #[serde(default)]
+        rotated: bool,
+        #[serde(rename = "spriteSourceSize")]
+        sprite_source_size: Option<RectData>,
+        #[serde(rename = "sourceSize")]
+        source_size: Option<SizeData>,
+        // milliseconds
+        duration: Option<f32>,
+        // 0.0 to 1.0 across the untrimmed frame
+        pivot: Option<PointData>,
+    }
+
+    #[derive(Deserialize)]
+    struct Meta {
+        image: String,
+        #[serde(rename = "frameTags", default)]
+        frame_tags: Vec<TagData>,
+        #[serde(default)]
+        slices: Vec<SliceData>,
+    }
+
+    #[derive(Deserialize)]
+    struct TagData {
+        name: String,
+        from: usize,
+        to: usize,
+        #[serde(default)]
+        direction: String,
+        // how many times to play, as a string; missing means forever
+        repeat: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    struct SliceData {
+        name: String,
+        keys: Vec<SliceKey>,
+    }
+
+    #[derive(Deserialize)]
+    struct SliceKey {
+        bounds: RectData,
+        center: Option<RectData>,
+        pivot: Option<PointData>,
+    }
+
+    #[derive(Deserialize, Copy, Clone)]
+    struct RectData {
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+    }
+
+    impl From<RectData> for Rect {
+        fn from(r: RectData) -> Self {
+            Rect::new(r.x, r.y, r.w, r.h)
+        }
+    }
+
+    #[derive(Deserialize)]
+    struct SizeData {
+        w: f32,
+        h: f32,
+    }
+
+    #[derive(Deserialize)]
+    struct PointData {
+        x: f32,
+        y: f32,
+    }
+
+    // "frames" is either a list of frames with filenames, or an object
+    // keyed by them whose order matters (so it can't go in a BTreeMap)
+    fn frames<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<(String, FrameData)>, D::Error> {
+        struct FramesVisitor;
+
+        impl<'de> Visitor<'de> for FramesVisitor {
+            type Value = Vec<(String, FrameData)>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a list or map of frames")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut frames = vec![];
+                while let Some(frame) = seq.next_element::<FrameData>()? {
+                    frames.push((frame.filename.clone().unwrap_or_default(), frame));
+                }
+                Ok(frames)
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut frames = vec![];
+                while let Some(entry) = map.next_entry::<String, FrameData>()? {
+                    frames.push(entry);
+                }
+                Ok(frames)
+            }
+        }
+
+        d.deserialize_any(FramesVisitor)
+    }
+
+    // Aseprite plays a tag forever unless it has a repeat count. Backward
+    // tags get their frames reversed, and counted ping-pong has its passes
+    // written out, since Playback::Times only counts forward passes.
+    fn tag_clip(tag: &TagData, mut frames: Vec<(Rect, f32)>) -> Result<AnimationData, SheetError> {
+        let times = match &tag.repeat {
+            None => None,
+            Some(repeat) => match repeat.parse::<u32>() {
+                Ok(times) if times > 0 => Some(times),
+                _ => return format_err(format!("tag {} has a bad repeat {:?}", tag.name, repeat)),
+            },
+        };
+        let (ping_pong, backward) = match tag.direction.as_str() {
+            "" | "forward" => (false, false),
+            "reverse" => (false, true),
+            "pingpong" => (true, false),
+            "pingpong_reverse" => (true, true),
+            other => return format_err(format!("unknown tag direction {}", other)),
+        };
+        if backward {
+            frames.reverse();
+        }
+        let playback = match (ping_pong, times) {
+            (false, None) => Playback::Loop,
+            (false, Some(times)) => Playback::Times(times),
+            (true, None) => Playback::PingPong,
+            (true, Some(times)) => {
+                frames = ping_pong_passes(&frames, times);
+                Playback::Once
+            }
+        };
+        Ok(AnimationData::new(frames, playback))
+    }
+
+    // `times` one-way passes, turning around without showing the end frames
+    // twice in a row, the way Aseprite counts ping-pong repeats
+    fn ping_pong_passes(frames: &[(Rect, f32)], times: u32) -> Vec<(Rect, f32)> {
+        let mut passes = frames.to_vec();
+        for pass in 1..times {
+            if pass % 2 == 1 {
+                passes.extend(frames.iter().rev().skip(1));
+            } else {
+                passes.extend(frames.iter().skip(1));
+            }
+        }
+        passes
+    }
+
+    pub fn convert(raw: Sheet, dir: &Path) -> Result<SpriteSheet, SheetError> {
+        let texture = Rc::new(Texture::with_file(&dir.join(&raw.meta.image))?);
+        let mut frames = vec![];
+        for (name, data) in raw.frames {
+            if data.rotated {
+                return format_err(format!("frame {} is rotated", name));
+            }
+            let rect = Rect::from(data.frame);
+            if !texture.valid_frame(rect) {
+                return format_err(format!("frame {} is outside the image", name));
+            }
+            let offset = data
+                .sprite_source_size
+                .map_or(Vec2::zero(), |r| Vec2::new(r.x, r.y));
+            let size = data
+                .source_size
+                .map_or(Vec2::new(rect.w, rect.h), |s| Vec2::new(s.w, s.h));
+            frames.push(SheetFrame {
+                name,
+                rect,
+                duration: data.duration.map_or(DEFAULT_FRAME_TIME, |ms| ms / 1000.0),
+                offset,
+                pivot: data.pivot.map(|p| Vec2::new(p.x * size.x, p.y * size.y)),
+            });
+        }
+
+        let mut clips = BTreeMap::new();
+        for frame in frames.iter() {
+            clips.insert(
+                frame.name.clone(),
+                Rc::new(AnimationData::new(
+                    vec![(frame.rect, frame.duration)],
+                    Playback::Once,
+                )),
+            );
+        }
+        for tag in raw.meta.frame_tags.iter() {
+            if tag.from > tag.to || tag.to >= frames.len() {
+                return format_err(format!("tag {} is out of range", tag.name));
+            }
+            let tagged = frames[tag.from..=tag.to]
+                .iter()
+                .map(|f| (f.rect, f.duration))
+                .collect();
+            clips.insert(tag.name.clone(), Rc::new(tag_clip(tag, tagged)?));
+        }
+        for (name, names) in raw.animations.iter() {
+            let mut animation = vec![];
+            for frame_name in names.iter() {
+                match frames.iter().find(|f| f.name == *frame_name) {
+                    Some(f) => animation.push((f.rect, f.duration)),
+                    None => {
+                        return format_err(format!(
+                            "animation {} has unknown frame {}",
+                            name, frame_name
+                        ))
+                    }
+                }
+            }
+            if animation.is_empty() {
+                return format_err(format!("animation {} has no frames", name));
+            }
+            clips.insert(
+                name.clone(),
+                Rc::new(AnimationData::new(animation, Playback::Loop)),
+            );
+        }
+
+        let slices = raw
+            .meta
+            .slices
+            .into_iter()
+            .filter_map(|slice| {
+                // keys are per frame; the first one is the slice as drawn
+                let key = slice.keys.into_iter().next()?;
+                Some(Slice {
+                    name: slice.name,
+                    bounds: key.bounds.into(),
+                    center: key.center.map(Rect::from),
+                    pivot: key.pivot.map(|p| Vec2::new(p.x, p.y)),
+                })
+            })
+            .collect();
+
+        Ok(SpriteSheet {
+            texture,
+            frames,
+            clips,
+            slices,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn content() -> &'static Path {
+        Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/content"))
+    }
+
+    #[test]
+    fn pigeon_flap_plays_once() {
+        let sheet = SpriteSheet::load(&content().join("pigeon.json")).unwrap();
+        let flap = sheet.clip("flap").unwrap();
+        assert_eq!(
+            flap.frames,
+            vec![
+                (Rect::new(20.0, 0.0, 20.0, 17.0), 0.22),
+                (Rect::new(40.0, 0.0, 20.0, 17.0), 0.02),
+            ]
+        );
+        assert_eq!(flap.playback, Playback::Times(1));
+        assert_eq!(
+            sheet.clip("glide").unwrap().frames,
+            vec![(Rect::new(0.0, 0.0, 20.0, 17.0), 0.1)]
+        );
+    }
+
+    // The x of each frame in tag "t" over frames at x 0, 20 and 40
+    fn tagged(direction: &str, repeat: Option<&str>) -> Result<(Vec<f32>, Playback), SheetError> {
+        let repeat = repeat.map_or(String::new(), |r| format!(r#", "repeat": "{}""#, r));
+        let text = format!(
+            r#"{{
+                "frames": {{
+                    "a": {{ "frame": {{ "x": 0, "y": 0, "w": 20, "h": 17 }}, "duration": 100 }},
+                    "b": {{ "frame": {{ "x": 20, "y": 0, "w": 20, "h": 17 }}, "duration": 100 }},
+                    "c": {{ "frame": {{ "x": 40, "y": 0, "w": 20, "h": 17 }}, "duration": 100 }}
+                }},
+                "meta": {{
+                    "image": "pigeon.png",
+                    "frameTags": [
+                        {{ "name": "t", "from": 0, "to": 2, "direction": "{}"{} }}
+                    ]
+                }}
+            }}"#,
+            direction, repeat
+        );
+        let sheet = SpriteSheet::from_json(&text, content())?;
+        let clip = sheet.clip("t").unwrap();
+        let xs = clip.frames.iter().map(|(rect, _)| rect.x).collect();
+        Ok((xs, clip.playback))
+    }
+
+    #[test]
+    fn tag_directions() {
+        let forward = vec![0.0, 20.0, 40.0];
+        let backward = vec![40.0, 20.0, 0.0];
+        let cases = [
+            ("forward", None, forward.clone(), Playback::Loop),
+            ("forward", Some("3"), forward.clone(), Playback::Times(3)),
+            ("reverse", None, backward.clone(), Playback::Loop),
+            ("reverse", Some("2"), backward.clone(), Playback::Times(2)),
+            ("pingpong", None, forward.clone(), Playback::PingPong),
+            ("pingpong", Some("1"), forward, Playback::Once),
+            (
+                "pingpong",
+                Some("3"),
+                vec![0.0, 20.0, 40.0, 20.0, 0.0, 20.0, 40.0],
+                Playback::Once,
+            ),
+            ("pingpong_reverse", None, backward, Playback::PingPong),
+            (
+                "pingpong_reverse",
+                Some("2"),
+                vec![40.0, 20.0, 0.0, 20.0, 40.0],
+                Playback::Once,
+            ),
+        ];
+        for (direction, repeat, frames, playback) in cases.iter() {
+            assert_eq!(
+                tagged(direction, *repeat).unwrap(),
+                (frames.clone(), *playback),
+                "{} {:?}",
+                direction,
+                repeat
+            );
+        }
+        for &(direction, repeat) in [("sideways", None), ("forward", Some("0"))].iter() {
+            assert!(
+                matches!(tagged(direction, repeat), Err(SheetError::Format(_))),
+                "{} {:?}",
+                direction,
+                repeat
+            );
+        }
+    }
+}
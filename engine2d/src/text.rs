@@ -51,8 +51,8 @@ impl<'fb> DrawTextExt for Screen<'fb> {
     // makes a bunch of assumptions, such as that all the characters are the same height. works because we're using a monospace/height font, won't necessarily work for others
     fn draw_text_at_pos(&mut self, string: &str, pos: Vec2, font: &TextInfo) {
         // starting positions
-        let mut x = pos.x as f32;
-        let y = pos.y as f32;
+        let mut x = pos.x;
+        let y = pos.y;
         for ch in string.chars() {
             if let Some(rect) = font.info.get(&ch) {
                 self.bitblt(&font.image, *rect, Vec2::new(x, y));
@@ -30,6 +30,16 @@ impl Texture {
             image,
         }
     }
+    // For pixels that are already premultiplied rgba8888, like a Canvas's
+    pub fn from_premultiplied(image: Vec<u8>, width: usize, height: usize) -> Self {
+        assert_eq!(image.len(), width * height * 4);
+        Self {
+            image,
+            width,
+            height,
+            depth: 4,
+        }
+    }
     pub fn depth(&self) -> usize {
         self.depth
     }
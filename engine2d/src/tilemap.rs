@@ -0,0 +1,1224 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::collision::{TileGrid, TileShape};
+use crate::objects::{Rect, Vec2};
+use crate::screen::{BlitTransform, Screen};
+use crate::texture::Texture;
+
+// Tiled keeps the flip flags in the top bits of each global tile id
+const FLIP_X: u32 = 0x8000_0000;
+const FLIP_Y: u32 = 0x4000_0000;
+const FLIP_DIAGONAL: u32 = 0x2000_0000;
+const GID_MASK: u32 = !(FLIP_X | FLIP_Y | FLIP_DIAGONAL);
+
+#[derive(Debug)]
+pub enum TilemapError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Xml(roxmltree::Error),
+    Image(image::error::ImageError),
+    // The file parsed but isn't a map we can use
+    Format(String),
+}
+
+impl fmt::Display for TilemapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "couldn't read map: {}", e),
+            Self::Json(e) => write!(f, "bad map json: {}", e),
+            Self::Xml(e) => write!(f, "bad map xml: {}", e),
+            Self::Image(e) => write!(f, "couldn't load tileset image: {}", e),
+            Self::Format(e) => write!(f, "unsupported map: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for TilemapError {}
+
+impl From<std::io::Error> for TilemapError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for TilemapError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Json(e)
+    }
+}
+
+impl From<roxmltree::Error> for TilemapError {
+    fn from(e: roxmltree::Error) -> Self {
+        Self::Xml(e)
+    }
+}
+
+impl From<image::error::ImageError> for TilemapError {
+    fn from(e: image::error::ImageError) -> Self {
+        Self::Image(e)
+    }
+}
+
+fn format_err<T>(msg: impl Into<String>) -> Result<T, TilemapError> {
+    Err(TilemapError::Format(msg.into()))
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Property {
+    Bool(bool),
+    Int(i64),
+    Float(f32),
+    // Strings, colors, files and anything else Tiled can store
+    String(String),
+}
+
+pub type Properties = BTreeMap<String, Property>;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Tile {
+    // Global id, i.e. offset by its tileset's first_gid
+    pub gid: u32,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    pub flip_diagonal: bool,
+}
+
+impl Tile {
+    // 0 means "no tile" in Tiled
+    pub fn from_raw(raw: u32) -> Option<Self> {
+        let gid = raw & GID_MASK;
+        if gid == 0 {
+            return None;
+        }
+        Some(Self {
+            gid,
+            flip_x: raw & FLIP_X != 0,
+            flip_y: raw & FLIP_Y != 0,
+            flip_diagonal: raw & FLIP_DIAGONAL != 0,
+        })
+    }
+
+    // How to get Tiled's flips out of bitblt_transformed, for a tile of this size.
+    // Tiled transposes first for the diagonal flip, then flips x and y.
+    fn blit_transform(&self, w: f32, h: f32) -> BlitTransform {
+        let mut xf = BlitTransform {
+            pivot: Vec2::new(w / 2.0, h / 2.0),
+            ..BlitTransform::default()
+        };
+        if self.flip_diagonal {
+            // a transpose is a quarter turn plus a flip
+            xf.rotation = std::f32::consts::FRAC_PI_2;
+            xf.flip_x = self.flip_y;
+            xf.flip_y = !self.flip_x;
+        } else {
+            xf.flip_x = self.flip_x;
+            xf.flip_y = self.flip_y;
+        }
+        xf
+    }
+}
+
+pub struct Tileset {
+    pub first_gid: u32,
+    pub texture: Rc<Texture>,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub columns: u32,
+    pub tile_count: u32,
+    pub margin: u32,
+    pub spacing: u32,
+    // Keyed by local tile id (gid - first_gid)
+    pub tile_properties: BTreeMap<u32, Properties>,
+}
+
+impl Tileset {
+    pub fn contains(&self, gid: u32) -> bool {
+        self.first_gid <= gid && gid < self.first_gid + self.tile_count
+    }
+
+    // Where tile `gid` lives in the texture
+    pub fn frame(&self, gid: u32) -> Rect {
+        let id = gid - self.first_gid;
+        let col = id % self.columns.max(1);
+        let row = id / self.columns.max(1);
+        Rect::new(
+            (self.margin + col * (self.tile_width + self.spacing)) as f32,
+            (self.margin + row * (self.tile_height + self.spacing)) as f32,
+            self.tile_width as f32,
+            self.tile_height as f32,
+        )
+    }
+}
+
+pub struct TileLayer {
+    pub name: String,
+    pub width: usize,
+    pub height: usize,
+    // Row-major, None for empty cells
+    pub tiles: Vec<Option<Tile>>,
+    // Drawing offset in pixels; solid-tile queries ignore it
+    pub offset: Vec2,
+    pub visible: bool,
+    pub properties: Properties,
+}
+
+impl TileLayer {
+    pub fn get(&self, col: usize, row: usize) -> Option<Tile> {
+        if col < self.width && row < self.height {
+            self.tiles[row * self.width + col]
+        } else {
+            None
+        }
+    }
+}
+
+pub struct MapObject {
+    pub id: u32,
+    pub name: String,
+    // Tiled's "type" (or "class" since Tiled 1.9)
+    pub kind: String,
+    // Top-left and size, even for tile objects (which Tiled anchors bottom-left)
+    pub rect: Rect,
+    pub tile: Option<Tile>,
+    pub properties: Properties,
+}
+
+pub struct ObjectLayer {
+    pub name: String,
+    pub objects: Vec<MapObject>,
+    pub offset: Vec2,
+    pub visible: bool,
+    pub properties: Properties,
+}
+
+pub enum Layer {
+    Tiles(TileLayer),
+    Objects(ObjectLayer),
+}
+
+impl Layer {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Tiles(l) => &l.name,
+            Self::Objects(l) => &l.name,
+        }
+    }
+}
+
+// An orthogonal, finite map from the Tiled editor (https://www.mapeditor.org).
+// Group layers get flattened into their children with offsets added up.
+pub struct Tilemap {
+    pub width: usize,
+    pub height: usize,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    // Sorted by first_gid
+    pub tilesets: Vec<Tileset>,
+    // Back to front
+    pub layers: Vec<Layer>,
+}
+
+impl Tilemap {
+    // Picks the format from the extension: .tmx/.xml is xml, anything else json
+    pub fn load(path: &Path) -> Result<Self, TilemapError> {
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let text = fs::read_to_string(path)?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("tmx") | Some("xml") => Self::from_tmx(&text, dir),
+            _ => Self::from_json(&text, dir),
+        }
+    }
+
+    // `dir` is where tileset and image paths in the map are relative to
+    pub fn from_json(text: &str, dir: &Path) -> Result<Self, TilemapError> {
+        let raw: json::Map = serde_json::from_str(text)?;
+        json::convert(raw, dir)?.check_slopes()
+    }
+
+    pub fn from_tmx(text: &str, dir: &Path) -> Result<Self, TilemapError> {
+        tmx::parse(text, dir)?.check_slopes()
+    }
+
+    // A slope is a floor, so it can be mirrored left to right, but there's
+    // no TileShape for one flipped upside down or turned on its side
+    fn check_slopes(self) -> Result<Self, TilemapError> {
+        for layer in self.tile_layers() {
+            let flipped = layer.tiles.iter().flatten().find(|tile| {
+                (tile.flip_y || tile.flip_diagonal) && self.slope(layer, **tile).is_some()
+            });
+            if let Some(tile) = flipped {
+                return format_err(format!(
+                    "slope tile {} in layer {} is flipped vertically or diagonally",
+                    tile.gid, layer.name
+                ));
+            }
+        }
+        Ok(self)
+    }
+
+    // The floor heights at a tile's left and right edges, from its own
+    // properties or its layer's, before any flip
+    fn slope(&self, layer: &TileLayer, tile: Tile) -> Option<(f32, f32)> {
+        let props = self.tile_properties(tile);
+        let get = |name| {
+            props
+                .and_then(|props| float(props, name))
+                .or_else(|| float(&layer.properties, name))
+        };
+        Some((get("slope_left")?, get("slope_right")?))
+    }
+
+    pub fn tile_layers(&self) -> impl Iterator<Item = &TileLayer> {
+        self.layers.iter().filter_map(|l| match l {
+            Layer::Tiles(t) => Some(t),
+            Layer::Objects(_) => None,
+        })
+    }
+
+    pub fn object_layers(&self) -> impl Iterator<Item = &ObjectLayer> {
+        self.layers.iter().filter_map(|l| match l {
+            Layer::Objects(o) => Some(o),
+            Layer::Tiles(_) => None,
+        })
+    }
+
+    pub fn layer_named(&self, name: &str) -> Option<&Layer> {
+        self.layers.iter().find(|l| l.name() == name)
+    }
+
+    pub fn tileset_for(&self, gid: u32) -> Option<&Tileset> {
+        self.tilesets.iter().rev().find(|ts| ts.contains(gid))
+    }
+
+    pub fn tile_properties(&self, tile: Tile) -> Option<&Properties> {
+        let ts = self.tileset_for(tile.gid)?;
+        ts.tile_properties.get(&(tile.gid - ts.first_gid))
+    }
+
+    // Size of the whole map in pixels
+    pub fn pixel_size(&self) -> Vec2 {
+        Vec2::new(
+            (self.width as u32 * self.tile_width) as f32,
+            (self.height as u32 * self.tile_height) as f32,
+        )
+    }
+
+    // Which cell a point falls in, if it's on the map
+    pub fn tile_coords(&self, p: Vec2) -> Option<(usize, usize)> {
+        if p.x < 0.0 || p.y < 0.0 {
+            return None;
+        }
+        let col = (p.x / self.tile_width as f32) as usize;
+        let row = (p.y / self.tile_height as f32) as usize;
+        if col < self.width && row < self.height {
+            Some((col, row))
+        } else {
+            None
+        }
+    }
+
+    pub fn tile_rect(&self, col: usize, row: usize) -> Rect {
+        Rect::new(
+            (col as u32 * self.tile_width) as f32,
+            (row as u32 * self.tile_height) as f32,
+            self.tile_width as f32,
+            self.tile_height as f32,
+        )
+    }
+
+    // A cell is solid if any tile layer has a tile there and either the tile
+    // or the whole layer has the bool property `solid` set
+    pub fn is_solid(&self, col: usize, row: usize) -> bool {
+        self.tile_layers().any(|layer| match layer.get(col, row) {
+            Some(tile) => {
+                is_true(&layer.properties, "solid")
+                    || self
+                        .tile_properties(tile)
+                        .is_some_and(|props| is_true(props, "solid"))
+            }
+            None => false,
+        })
+    }
+
+    // Cells overlapping `area` (in map pixels), clamped to the map
+    pub fn cells_in(&self, area: Rect) -> impl Iterator<Item = (usize, usize)> {
+        let tw = self.tile_width as f32;
+        let th = self.tile_height as f32;
+        let col0 = (area.x / tw).floor().max(0.0) as usize;
+        let row0 = (area.y / th).floor().max(0.0) as usize;
+        let col1 = ((area.x + area.w) / tw)
+            .ceil()
+            .max(0.0)
+            .min(self.width as f32) as usize;
+        let row1 = ((area.y + area.h) / th)
+            .ceil()
+            .max(0.0)
+            .min(self.height as f32) as usize;
+        (row0..row1).flat_map(move |row| (col0..col1).map(move |col| (col, row)))
+    }
+
+    // Rects of every solid cell touching `area`, e.g. for collision::gather_contacts
+    pub fn solid_rects_in(&self, area: Rect) -> Vec<Rect> {
+        self.cells_in(area)
+            .filter(|&(col, row)| self.is_solid(col, row))
+            .map(|(col, row)| self.tile_rect(col, row))
+            .collect()
+    }
+}
+
+// Tile properties (or the whole layer's) pick the shape:
+// `solid` and `one_way` are bools, slopes set the floats `slope_left` and
+// `slope_right` to the floor height at each edge (0 bottom, 1 top).
+// Flipping a slope tile across swaps its edges.
+// When layers disagree, solid beats slopes, which beat one-way tiles.
+impl TileGrid for Tilemap {
+    fn tile_size(&self) -> Vec2 {
+        Vec2::new(self.tile_width as f32, self.tile_height as f32)
+    }
+
+    fn shape_at(&self, col: i32, row: i32) -> TileShape {
+        if col < 0 || row < 0 {
+            return TileShape::Empty;
+        }
+        let (col, row) = (col as usize, row as usize);
+        let mut shape = TileShape::Empty;
+        for layer in self.tile_layers() {
+            let tile = match layer.get(col, row) {
+                Some(tile) => tile,
+                None => continue,
+            };
+            let empty = Properties::new();
+            let props = self.tile_properties(tile).unwrap_or(&empty);
+            let flag = |name| is_true(props, name) || is_true(&layer.properties, name);
+            if flag("solid") {
+                return TileShape::Solid;
+            }
+            if let Some((left, right)) = self.slope(layer, tile) {
+                shape = if tile.flip_x {
+                    TileShape::Slope {
+                        left: right,
+                        right: left,
+                    }
+                } else {
+                    TileShape::Slope { left, right }
+                };
+            } else if flag("one_way") && shape == TileShape::Empty {
+                shape = TileShape::OneWay;
+            }
+        }
+        shape
+    }
+}
+
+fn float(props: &Properties, name: &str) -> Option<f32> {
+    match props.get(name) {
+        Some(Property::Float(f)) => Some(*f),
+        Some(Property::Int(i)) => Some(*i as f32),
+        _ => None,
+    }
+}
+
+fn is_true(props: &Properties, name: &str) -> bool {
+    matches!(props.get(name), Some(Property::Bool(true)))
+}
+
+fn parse_property(kind: &str, value: &str) -> Property {
+    match kind {
+        "bool" => Property::Bool(value == "true"),
+        "int" | "object" => value
+            .parse()
+            .map_or_else(|_| Property::String(value.to_string()), Property::Int),
+        "float" => value
+            .parse()
+            .map_or_else(|_| Property::String(value.to_string()), Property::Float),
+        _ => Property::String(value.to_string()),
+    }
+}
+
+// Layer data is either csv or (uncompressed) base64 little-endian u32s
+fn decode_tiles(
+    data: &str,
+    encoding: Option<&str>,
+    compression: Option<&str>,
+) -> Result<Vec<Option<Tile>>, TilemapError> {
+    if let Some(c) = compression.filter(|c| !c.is_empty()) {
+        return format_err(format!(
+            "{} compressed layers, save the map with uncompressed layer data",
+            c
+        ));
+    }
+    match encoding {
+        Some("base64") => {
+            let bytes = base64::decode(data.trim())
+                .map_err(|e| TilemapError::Format(format!("bad base64 layer: {}", e)))?;
+            Ok(bytes
+                .chunks_exact(4)
+                .map(|b| Tile::from_raw(u32::from_le_bytes([b[0], b[1], b[2], b[3]])))
+                .collect())
+        }
+        Some("csv") | None => data
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| match s.parse() {
+                Ok(raw) => Ok(Tile::from_raw(raw)),
+                Err(_) => format_err(format!("bad tile id {:?}", s)),
+            })
+            .collect(),
+        Some(other) => format_err(format!("unknown layer encoding {}", other)),
+    }
+}
+
+fn load_texture(dir: &Path, image: &str) -> Result<Rc<Texture>, TilemapError> {
+    if image.is_empty() {
+        return format_err("image collection tilesets aren't supported");
+    }
+    Ok(Rc::new(Texture::with_file(&dir.join(image))?))
+}
+
+// Tiled always writes columns and tilecount, but older or hand-written
+// tilesets can leave them out; without a count no gid would ever match the
+// tileset, so work them out from the image the way Tiled does
+fn fill_in_grid(mut ts: Tileset) -> Result<Tileset, TilemapError> {
+    if ts.tile_width == 0 || ts.tile_height == 0 {
+        return format_err(format!("tileset at gid {} has no tile size", ts.first_gid));
+    }
+    let (w, h) = ts.texture.size();
+    let (margin, spacing) = (ts.margin, ts.spacing);
+    let fit = |size: usize, tile: u32| {
+        (size as u32 + spacing).saturating_sub(2 * margin) / (tile + spacing)
+    };
+    if ts.columns == 0 {
+        ts.columns = fit(w, ts.tile_width);
+    }
+    if ts.tile_count == 0 {
+        ts.tile_count = ts.columns * fit(h, ts.tile_height);
+    }
+    Ok(ts)
+}
+
+fn check_layer_size(
+    name: &str,
+    tiles: &[Option<Tile>],
+    w: usize,
+    h: usize,
+) -> Result<(), TilemapError> {
+    if tiles.len() != w * h {
+        return format_err(format!(
+            "layer {} has {} tiles, expected {}x{}",
+            name,
+            tiles.len(),
+            w,
+            h
+        ));
+    }
+    Ok(())
+}
+
+// Tiled's JSON format (.tmj, .json, and .tsj for tilesets)
+mod json {
+    use super::*;
+
+    #[derive(Deserialize)]
+    pub struct Map {
+        width: usize,
+        height: usize,
+        tilewidth: u32,
+        tileheight: u32,
+        #[serde(default)]
+        infinite: bool,
+        #[serde(default)]
+        orientation: Option<String>,
+        layers: Vec<LayerData>,
+        #[serde(default)]
+        tilesets: Vec<TilesetRef>,
+    }
+
+    #[derive(Deserialize)]
+    struct LayerData {
+        #[serde(rename = "type")]
+        kind: String,
+        #[serde(default)]
+        name: String,
+        #[serde(default)]
+        width: usize,
+        #[serde(default)]
+        height: usize,
+        data: Option<serde_json::Value>,
+        encoding: Option<String>,
+        compression: Option<String>,
+        #[serde(default)]
+        offsetx: f32,
+        #[serde(default)]
+        offsety: f32,
+        #[serde(default = "visible")]
+        visible: bool,
+        #[serde(default)]
+        objects: Vec<Object>,
+        #[serde(default)]
+        layers: Vec<LayerData>,
+        #[serde(default)]
+        properties: Vec<PropertyData>,
+    }
+
+    fn visible() -> bool {
+        true
+    }
+
+    #[derive(Deserialize)]
+    struct TilesetRef {
+        firstgid: u32,
+        source: Option<String>,
+        #[serde(flatten)]
+        tileset: TilesetData,
+    }
+
+    #[derive(Deserialize, Default)]
+    #[serde(default)]
+    struct TilesetData {
+        tilewidth: u32,
+        tileheight: u32,
+        columns: u32,
+        tilecount: u32,
+        margin: u32,
+        spacing: u32,
+        image: String,
+        tiles: Vec<TileData>,
+    }
+
+    #[derive(Deserialize)]
+    struct TileData {
+        id: u32,
+        #[serde(default)]
+        properties: Vec<PropertyData>,
+    }
+
+    #[derive(Deserialize)]
+    struct PropertyData {
+        name: String,
+        #[serde(rename = "type", default)]
+        kind: String,
+        value: serde_json::Value,
+    }
+
+    #[derive(Deserialize)]
+    struct Object {
+        #[serde(default)]
+        id: u32,
+        #[serde(default)]
+        name: String,
+        #[serde(rename = "type", alias = "class", default)]
+        kind: String,
+        #[serde(default)]
+        x: f32,
+        #[serde(default)]
+        y: f32,
+        #[serde(default)]
+        width: f32,
+        #[serde(default)]
+        height: f32,
+        gid: Option<u32>,
+        #[serde(default)]
+        properties: Vec<PropertyData>,
+    }
+
+    fn properties(raw: Vec<PropertyData>) -> Properties {
+        raw.into_iter()
+            .map(|p| {
+                let value = match p.value {
+                    serde_json::Value::String(s) => s,
+                    other => other.to_string(),
+                };
+                (p.name, parse_property(&p.kind, &value))
+            })
+            .collect()
+    }
+
+    fn tileset(first_gid: u32, raw: TilesetData, dir: &Path) -> Result<Tileset, TilemapError> {
+        fill_in_grid(Tileset {
+            first_gid,
+            texture: load_texture(dir, &raw.image)?,
+            tile_width: raw.tilewidth,
+            tile_height: raw.tileheight,
+            columns: raw.columns,
+            tile_count: raw.tilecount,
+            margin: raw.margin,
+            spacing: raw.spacing,
+            tile_properties: raw
+                .tiles
+                .into_iter()
+                .map(|t| (t.id, properties(t.properties)))
+                .collect(),
+        })
+    }
+
+    fn layers(
+        raw: Vec<LayerData>,
+        offset: Vec2,
+        into: &mut Vec<Layer>,
+    ) -> Result<(), TilemapError> {
+        for l in raw {
+            let offset = Vec2::new(offset.x + l.offsetx, offset.y + l.offsety);
+            match l.kind.as_str() {
+                "tilelayer" => {
+                    let tiles = match l.data {
+                        Some(serde_json::Value::Array(ids)) => ids
+                            .iter()
+                            .map(|id| match id.as_u64() {
+                                Some(raw) => Ok(Tile::from_raw(raw as u32)),
+                                None => format_err(format!("bad tile id {}", id)),
+                            })
+                            .collect::<Result<Vec<_>, _>>()?,
+                        Some(serde_json::Value::String(s)) => {
+                            decode_tiles(&s, l.encoding.as_deref(), l.compression.as_deref())?
+                        }
+                        _ => return format_err(format!("layer {} has no tile data", l.name)),
+                    };
+                    check_layer_size(&l.name, &tiles, l.width, l.height)?;
+                    into.push(Layer::Tiles(TileLayer {
+                        name: l.name,
+                        width: l.width,
+                        height: l.height,
+                        tiles,
+                        offset,
+                        visible: l.visible,
+                        properties: properties(l.properties),
+                    }));
+                }
+                "objectgroup" => {
+                    let objects = l
+                        .objects
+                        .into_iter()
+                        .map(|o| {
+                            let tile = o.gid.and_then(Tile::from_raw);
+                            // tile objects hang up from their y
+                            let y = if tile.is_some() { o.y - o.height } else { o.y };
+                            MapObject {
+                                id: o.id,
+                                name: o.name,
+                                kind: o.kind,
+                                rect: Rect::new(o.x, y, o.width, o.height),
+                                tile,
+                                properties: properties(o.properties),
+                            }
+                        })
+                        .collect();
+                    into.push(Layer::Objects(ObjectLayer {
+                        name: l.name,
+                        objects,
+                        offset,
+                        visible: l.visible,
+                        properties: properties(l.properties),
+                    }));
+                }
+                "group" => layers(l.layers, offset, into)?,
+                // image layers aren't tiles or colliders, skip them
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    pub fn convert(raw: Map, dir: &Path) -> Result<Tilemap, TilemapError> {
+        if raw.infinite {
+            return format_err("infinite maps aren't supported");
+        }
+        if let Some(o) = raw.orientation.as_deref().filter(|&o| o != "orthogonal") {
+            return format_err(format!("{} maps aren't supported", o));
+        }
+        let mut tilesets = Vec::new();
+        for ts in raw.tilesets {
+            tilesets.push(match ts.source {
+                Some(source) => load_tileset(ts.firstgid, &dir.join(source))?,
+                None => tileset(ts.firstgid, ts.tileset, dir)?,
+            });
+        }
+        tilesets.sort_by_key(|ts| ts.first_gid);
+        let mut out = Vec::new();
+        layers(raw.layers, Vec2::new(0.0, 0.0), &mut out)?;
+        Ok(Tilemap {
+            width: raw.width,
+            height: raw.height,
+            tile_width: raw.tilewidth,
+            tile_height: raw.tileheight,
+            tilesets,
+            layers: out,
+        })
+    }
+
+    // External tileset, either .tsj/.json or .tsx
+    pub fn load_tileset(first_gid: u32, path: &Path) -> Result<Tileset, TilemapError> {
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let text = fs::read_to_string(path)?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("tsx") | Some("xml") => tmx::parse_tileset_file(first_gid, &text, dir),
+            _ => tileset(first_gid, serde_json::from_str(&text)?, dir),
+        }
+    }
+}
+
+// Tiled's XML format (.tmx, and .tsx for tilesets)
+mod tmx {
+    use super::*;
+    use roxmltree::{Document, Node};
+
+    fn attr<T: FromStr>(node: Node, name: &str) -> Result<T, TilemapError> {
+        match node.attribute(name).map(str::parse) {
+            Some(Ok(v)) => Ok(v),
+            Some(Err(_)) => format_err(format!("bad {} on <{}>", name, node.tag_name().name())),
+            None => format_err(format!("missing {} on <{}>", name, node.tag_name().name())),
+        }
+    }
+
+    fn attr_or<T: FromStr>(node: Node, name: &str, default: T) -> T {
+        node.attribute(name)
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(default)
+    }
+
+    fn children<'a, 'input: 'a>(
+        node: Node<'a, 'input>,
+        tag: &'static str,
+    ) -> impl Iterator<Item = Node<'a, 'input>> {
+        node.children().filter(move |n| n.has_tag_name(tag))
+    }
+
+    fn properties(node: Node) -> Properties {
+        children(node, "properties")
+            .flat_map(|props| children(props, "property"))
+            .map(|p| {
+                let name = p.attribute("name").unwrap_or("").to_string();
+                // long strings go in the element text instead of value=
+                let value = p.attribute("value").or_else(|| p.text()).unwrap_or("");
+                (
+                    name,
+                    parse_property(p.attribute("type").unwrap_or(""), value),
+                )
+            })
+            .collect()
+    }
+
+    fn tileset(first_gid: u32, node: Node, dir: &Path) -> Result<Tileset, TilemapError> {
+        let image = children(node, "image")
+            .next()
+            .and_then(|i| i.attribute("source"))
+            .unwrap_or("");
+        fill_in_grid(Tileset {
+            first_gid,
+            texture: load_texture(dir, image)?,
+            tile_width: attr(node, "tilewidth")?,
+            tile_height: attr(node, "tileheight")?,
+            columns: attr_or(node, "columns", 0),
+            tile_count: attr_or(node, "tilecount", 0),
+            margin: attr_or(node, "margin", 0),
+            spacing: attr_or(node, "spacing", 0),
+            tile_properties: children(node, "tile")
+                .map(|t| Ok((attr(t, "id")?, properties(t))))
+                .collect::<Result<_, TilemapError>>()?,
+        })
+    }
+
+    pub fn parse_tileset_file(
+        first_gid: u32,
+        text: &str,
+        dir: &Path,
+    ) -> Result<Tileset, TilemapError> {
+        let doc = Document::parse(text)?;
+        tileset(first_gid, doc.root_element(), dir)
+    }
+
+    fn layers(node: Node, offset: Vec2, into: &mut Vec<Layer>) -> Result<(), TilemapError> {
+        for l in node.children().filter(|n| n.is_element()) {
+            let name = l.attribute("name").unwrap_or("").to_string();
+            let offset = Vec2::new(
+                offset.x + attr_or(l, "offsetx", 0.0),
+                offset.y + attr_or(l, "offsety", 0.0),
+            );
+            let visible = attr_or(l, "visible", 1) != 0;
+            match l.tag_name().name() {
+                "layer" => {
+                    let width = attr(l, "width")?;
+                    let height = attr(l, "height")?;
+                    let data = match children(l, "data").next() {
+                        Some(d) => d,
+                        None => return format_err(format!("layer {} has no data", name)),
+                    };
+                    if children(data, "chunk").next().is_some() {
+                        return format_err("infinite maps aren't supported");
+                    }
+                    let tiles = if data.attribute("encoding").is_none() {
+                        // the old one-<tile>-per-cell format
+                        children(data, "tile")
+                            .map(|t| Tile::from_raw(attr_or(t, "gid", 0)))
+                            .collect()
+                    } else {
+                        decode_tiles(
+                            data.text().unwrap_or(""),
+                            data.attribute("encoding"),
+                            data.attribute("compression"),
+                        )?
+                    };
+                    check_layer_size(&name, &tiles, width, height)?;
+                    into.push(Layer::Tiles(TileLayer {
+                        name,
+                        width,
+                        height,
+                        tiles,
+                        offset,
+                        visible,
+                        properties: properties(l),
+                    }));
+                }
+                "objectgroup" => {
+                    let objects = children(l, "object")
+                        .map(|o| {
+                            let tile = Tile::from_raw(attr_or(o, "gid", 0));
+                            let w = attr_or(o, "width", 0.0);
+                            let h = attr_or(o, "height", 0.0);
+                            let y = attr_or(o, "y", 0.0);
+                            // tile objects hang up from their y
+                            let y = if tile.is_some() { y - h } else { y };
+                            MapObject {
+                                id: attr_or(o, "id", 0),
+                                name: o.attribute("name").unwrap_or("").to_string(),
+                                kind: o
+                                    .attribute("type")
+                                    .or_else(|| o.attribute("class"))
+                                    .unwrap_or("")
+                                    .to_string(),
+                                rect: Rect::new(attr_or(o, "x", 0.0), y, w, h),
+                                tile,
+                                properties: properties(o),
+                            }
+                        })
+                        .collect();
+                    into.push(Layer::Objects(ObjectLayer {
+                        name,
+                        objects,
+                        offset,
+                        visible,
+                        properties: properties(l),
+                    }));
+                }
+                "group" => layers(l, offset, into)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    pub fn parse(text: &str, dir: &Path) -> Result<Tilemap, TilemapError> {
+        let doc = Document::parse(text)?;
+        let map = doc.root_element();
+        if !map.has_tag_name("map") {
+            return format_err("root element isn't <map>");
+        }
+        if attr_or(map, "infinite", 0) != 0 {
+            return format_err("infinite maps aren't supported");
+        }
+        if let Some(o) = map.attribute("orientation").filter(|&o| o != "orthogonal") {
+            return format_err(format!("{} maps aren't supported", o));
+        }
+        let mut tilesets = Vec::new();
+        for ts in children(map, "tileset") {
+            let first_gid = attr(ts, "firstgid")?;
+            tilesets.push(match ts.attribute("source") {
+                Some(source) => json::load_tileset(first_gid, &dir.join(source))?,
+                None => tileset(first_gid, ts, dir)?,
+            });
+        }
+        tilesets.sort_by_key(|ts| ts.first_gid);
+        let mut out = Vec::new();
+        layers(map, Vec2::new(0.0, 0.0), &mut out)?;
+        Ok(Tilemap {
+            width: attr(map, "width")?,
+            height: attr(map, "height")?,
+            tile_width: attr(map, "tilewidth")?,
+            tile_height: attr(map, "tileheight")?,
+            tilesets,
+            layers: out,
+        })
+    }
+}
+
+pub trait DrawTilemapExt {
+    // Every visible tile layer, back to front
+    fn draw_tilemap(&mut self, map: &Tilemap);
+    fn draw_tile_layer(&mut self, map: &Tilemap, layer: &TileLayer);
+}
+
+impl<'fb> DrawTilemapExt for Screen<'fb> {
+    fn draw_tilemap(&mut self, map: &Tilemap) {
+        for layer in map.tile_layers().filter(|l| l.visible) {
+            self.draw_tile_layer(map, layer);
+        }
+    }
+
+    fn draw_tile_layer(&mut self, map: &Tilemap, layer: &TileLayer) {
+        let tw = map.tile_width as f32;
+        let th = map.tile_height as f32;
+        // Only walk the cells the camera can see, plus one for big tiles
+        let view = self.camera().view(self.size());
+        let col0 = ((view.x - layer.offset.x) / tw).floor() - 1.0;
+        let row0 = ((view.y - layer.offset.y) / th).floor() - 1.0;
+        let col1 = ((view.x + view.w - layer.offset.x) / tw).ceil() + 1.0;
+        let row1 = ((view.y + view.h - layer.offset.y) / th).ceil() + 1.0;
+        let col0 = col0.max(0.0) as usize;
+        let row0 = row0.max(0.0) as usize;
+        let col1 = col1.max(0.0).min(layer.width as f32) as usize;
+        let row1 = row1.max(0.0).min(layer.height as f32) as usize;
+        for row in row0..row1 {
+            for col in col0..col1 {
+                let tile = match layer.get(col, row) {
+                    Some(tile) => tile,
+                    None => continue,
+                };
+                let ts = match map.tileset_for(tile.gid) {
+                    Some(ts) => ts,
+                    None => continue,
+                };
+                let frame = ts.frame(tile.gid);
+                if !ts.texture.valid_frame(frame) {
+                    continue;
+                }
+                // Tiles bigger than the grid stick up out of their cell
+                let to = Vec2::new(
+                    layer.offset.x + col as f32 * tw,
+                    layer.offset.y + (row + 1) as f32 * th - frame.h,
+                );
+                let xf = tile.blit_transform(frame.w, frame.h);
+                self.bitblt_transformed(&ts.texture, frame, to, xf);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn content() -> &'static Path {
+        Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/content"))
+    }
+
+    #[test]
+    fn missing_tile_count_comes_from_the_image() {
+        // tileset2.png is 240x360
+        let tmx = r#"<map orientation="orthogonal" width="1" height="1" tilewidth="20" tileheight="20">
+            <tileset firstgid="1" tilewidth="20" tileheight="20" spacing="2" margin="1">
+                <image source="tileset2.png" width="240" height="360"/>
+            </tileset>
+            <layer name="a" width="1" height="1"><data encoding="csv">99</data></layer>
+        </map>"#;
+        let map = Tilemap::from_tmx(tmx, content()).unwrap();
+        let ts = &map.tilesets[0];
+        // (240 - 2 + 2) / 22 across, (360 - 2 + 2) / 22 down
+        assert_eq!((ts.columns, ts.tile_count), (10, 160));
+        assert!(map.tileset_for(99).is_some());
+        assert_eq!(ts.frame(12), Rect::new(1.0 + 22.0, 1.0 + 22.0, 20.0, 20.0));
+
+        let json = r#"{ "width": 1, "height": 1, "tilewidth": 20, "tileheight": 20,
+            "layers": [],
+            "tilesets": [{ "firstgid": 1, "tilewidth": 20, "tileheight": 20, "image": "tileset2.png" }] }"#;
+        let map = Tilemap::from_json(json, content()).unwrap();
+        assert_eq!(
+            (map.tilesets[0].columns, map.tilesets[0].tile_count),
+            (12, 216)
+        );
+    }
+
+    fn tile(gid: u32, flip_x: bool, flip_y: bool, flip_diagonal: bool) -> Option<Tile> {
+        Some(Tile {
+            gid,
+            flip_x,
+            flip_y,
+            flip_diagonal,
+        })
+    }
+
+    #[test]
+    fn city_tmx() {
+        let map = Tilemap::load(&content().join("city.tmx")).unwrap();
+        assert_eq!((map.width, map.height), (24, 18));
+        assert_eq!(map.pixel_size(), Vec2::new(480.0, 360.0));
+        assert_eq!(map.tilesets[0].tile_count, 216);
+
+        // the right half is the left half mirrored
+        let city = map.tile_layers().next().unwrap();
+        assert_eq!(city.name, "city");
+        assert_eq!(city.get(0, 0), tile(1, false, false, false));
+        assert_eq!(city.get(23, 0), tile(1, true, false, false));
+        assert_eq!(city.get(12, 17), tile(216, true, false, false));
+        let flipped = city.get(12, 17).unwrap();
+        assert_eq!(
+            map.tileset_for(flipped.gid).unwrap().frame(flipped.gid),
+            Rect::new(220.0, 340.0, 20.0, 20.0)
+        );
+
+        let ground = match map.layer_named("ground") {
+            Some(Layer::Tiles(layer)) => layer,
+            _ => panic!("no ground layer"),
+        };
+        assert!(!ground.visible);
+        assert_eq!(ground.properties.get("solid"), Some(&Property::Bool(true)));
+        assert_eq!(map.shape_at(5, 14), TileShape::Solid);
+        assert_eq!(map.shape_at(5, 13), TileShape::Empty);
+        assert_eq!(map.shape_at(7, 11), TileShape::OneWay);
+        assert_eq!(map.shape_at(16, 11), TileShape::OneWay);
+        assert_eq!(map.shape_at(-1, 14), TileShape::Empty);
+
+        let objects = map.object_layers().next().unwrap();
+        assert_eq!(objects.name, "objects");
+        let spawn = &objects.objects[0];
+        assert_eq!((spawn.id, spawn.name.as_str()), (1, "spawn"));
+        assert_eq!(spawn.rect, Rect::new(110.0, 280.0, 0.0, 0.0));
+        let goal = &objects.objects[1];
+        assert_eq!((goal.name.as_str(), goal.kind.as_str()), ("goal", "goal"));
+        assert_eq!(goal.rect, Rect::new(260.0, 120.0, 20.0, 20.0));
+        assert_eq!(goal.tile, None);
+    }
+
+    #[test]
+    fn json_map() {
+        // base64 data holding 1, 2 flipped across, 3 flipped down and
+        // diagonally, and an empty cell
+        let json = r#"{
+            "width": 2, "height": 2, "tilewidth": 20, "tileheight": 20,
+            "orientation": "orthogonal", "infinite": false,
+            "tilesets": [{
+                "firstgid": 1, "tilewidth": 20, "tileheight": 20,
+                "columns": 12, "tilecount": 216, "image": "tileset2.png",
+                "tiles": [{ "id": 1, "properties": [
+                    { "name": "slope_left", "type": "float", "value": 0.25 },
+                    { "name": "slope_right", "type": "float", "value": 1 }
+                ]}]
+            }],
+            "layers": [
+                { "type": "group", "name": "stuff", "offsetx": 5, "offsety": 1, "layers": [
+                    { "type": "tilelayer", "name": "tiles", "width": 2, "height": 2,
+                      "encoding": "base64", "data": "AQAAAAIAAIADAABgAAAAAA==",
+                      "offsetx": 1, "properties": [
+                          { "name": "label", "type": "string", "value": "hi" },
+                          { "name": "depth", "type": "int", "value": 3 }
+                      ] }
+                ] },
+                { "type": "objectgroup", "name": "things", "visible": false, "objects": [
+                    { "id": 4, "name": "sign", "class": "prop", "gid": 2147483649,
+                      "x": 10, "y": 40, "width": 20, "height": 20,
+                      "properties": [{ "name": "lit", "type": "bool", "value": true }] },
+                    { "id": 5, "name": "zone", "x": 0, "y": 0, "width": 40, "height": 10 }
+                ] },
+                { "type": "imagelayer", "name": "backdrop" }
+            ]
+        }"#;
+        let map = Tilemap::from_json(json, content()).unwrap();
+        assert_eq!(map.layers.len(), 2);
+
+        let tiles = map.tile_layers().next().unwrap();
+        assert_eq!(tiles.offset, Vec2::new(6.0, 1.0));
+        assert_eq!(tiles.get(0, 0), tile(1, false, false, false));
+        assert_eq!(tiles.get(1, 0), tile(2, true, false, false));
+        assert_eq!(tiles.get(0, 1), tile(3, false, true, true));
+        assert_eq!(tiles.get(1, 1), None);
+        assert_eq!(
+            tiles.properties.get("label"),
+            Some(&Property::String("hi".to_string()))
+        );
+        assert_eq!(tiles.properties.get("depth"), Some(&Property::Int(3)));
+        let props = map.tile_properties(tiles.get(1, 0).unwrap()).unwrap();
+        assert_eq!(props.get("slope_right"), Some(&Property::Float(1.0)));
+        // flipped across, so it rises to the left
+        assert_eq!(
+            map.shape_at(1, 0),
+            TileShape::Slope {
+                left: 1.0,
+                right: 0.25
+            }
+        );
+
+        let things = map.object_layers().next().unwrap();
+        assert!(!things.visible);
+        let sign = &things.objects[0];
+        assert_eq!((sign.id, sign.kind.as_str()), (4, "prop"));
+        assert_eq!(sign.tile, tile(1, true, false, false));
+        // tile objects are anchored at their bottom-left
+        assert_eq!(sign.rect, Rect::new(10.0, 20.0, 20.0, 20.0));
+        assert_eq!(sign.properties.get("lit"), Some(&Property::Bool(true)));
+        assert_eq!(things.objects[1].rect, Rect::new(0.0, 0.0, 40.0, 10.0));
+    }
+
+    #[test]
+    fn unsupported_maps() {
+        let bad = |json: &str| {
+            matches!(
+                Tilemap::from_json(json, content()),
+                Err(TilemapError::Format(_))
+            )
+        };
+        assert!(bad(
+            r#"{ "width": 1, "height": 1, "tilewidth": 20, "tileheight": 20,
+            "infinite": true, "layers": [] }"#
+        ));
+        assert!(bad(
+            r#"{ "width": 1, "height": 1, "tilewidth": 20, "tileheight": 20,
+            "orientation": "isometric", "layers": [] }"#
+        ));
+        assert!(bad(
+            r#"{ "width": 2, "height": 1, "tilewidth": 20, "tileheight": 20,
+            "layers": [{ "type": "tilelayer", "name": "short", "width": 2, "height": 1, "data": [1] }] }"#
+        ));
+        assert!(bad(
+            r#"{ "width": 1, "height": 1, "tilewidth": 20, "tileheight": 20,
+            "layers": [{ "type": "tilelayer", "name": "z", "width": 1, "height": 1,
+                "encoding": "base64", "compression": "zlib", "data": "eJw=" }] }"#
+        ));
+    }
+
+    fn ramp(raw: u32) -> Result<Tilemap, TilemapError> {
+        let tmx = format!(
+            r#"<map orientation="orthogonal" width="2" height="1" tilewidth="20" tileheight="20">
+                <tileset firstgid="1" tilewidth="20" tileheight="20" tilecount="216" columns="12">
+                    <image source="tileset2.png" width="240" height="360"/>
+                </tileset>
+                <layer name="ramp" width="2" height="1">
+                    <properties>
+                        <property name="slope_left" type="float" value="0"/>
+                        <property name="slope_right" type="float" value="0.5"/>
+                    </properties>
+                    <data encoding="csv">1,{}</data>
+                </layer>
+            </map>"#,
+            raw
+        );
+        Tilemap::from_tmx(&tmx, content())
+    }
+
+    #[test]
+    fn flipped_slopes() {
+        let map = ramp(1 | FLIP_X).unwrap();
+        assert_eq!(
+            map.shape_at(0, 0),
+            TileShape::Slope {
+                left: 0.0,
+                right: 0.5
+            }
+        );
+        assert_eq!(
+            map.shape_at(1, 0),
+            TileShape::Slope {
+                left: 0.5,
+                right: 0.0
+            }
+        );
+        for &flip in [FLIP_Y, FLIP_DIAGONAL, FLIP_X | FLIP_Y].iter() {
+            assert!(matches!(ramp(1 | flip), Err(TilemapError::Format(_))));
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use crate::objects::Vec2;
+use serde::{Deserialize, Serialize};
+
+// Scale, then rotate, then translate. Rotation is in radians and, like
+// BlitTransform's, turns clockwise on screen since y points down.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Transform2D {
+    pub translation: Vec2,
+    pub rotation: f32,
+    pub scale: Vec2,
+}
+
+impl Default for Transform2D {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Transform2D {
+    pub fn new(translation: Vec2, rotation: f32, scale: Vec2) -> Self {
+        Self {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    pub fn identity() -> Self {
+        Self::new(Vec2::zero(), 0.0, Vec2::new(1.0, 1.0))
+    }
+
+    pub fn from_translation(translation: Vec2) -> Self {
+        Self {
+            translation,
+            ..Self::identity()
+        }
+    }
+
+    // Only scales and rotates; for directions and offsets
+    pub fn apply_vector(&self, v: Vec2) -> Vec2 {
+        let (sin, cos) = self.rotation.sin_cos();
+        let (x, y) = (v.x * self.scale.x, v.y * self.scale.y);
+        Vec2::new(x * cos - y * sin, x * sin + y * cos)
+    }
+
+    pub fn apply(&self, p: Vec2) -> Vec2 {
+        self.translation + self.apply_vector(p)
+    }
+
+    // Undoes apply, e.g. to get a mouse position into a node's local space
+    pub fn inverse_apply(&self, p: Vec2) -> Vec2 {
+        let (sin, cos) = self.rotation.sin_cos();
+        let d = p - self.translation;
+        let (x, y) = (d.x * cos + d.y * sin, -d.x * sin + d.y * cos);
+        Vec2::new(x / self.scale.x, y / self.scale.y)
+    }
+
+    // `child` given relative to self, turned into the space self is in.
+    // Rotations add up and scales multiply, which is exact unless a parent
+    // with uneven scale has a rotated child (that would need shearing).
+    pub fn compose(&self, child: &Transform2D) -> Transform2D {
+        Transform2D {
+            translation: self.apply(child.translation),
+            rotation: self.rotation + child.rotation,
+            scale: Vec2::new(self.scale.x * child.scale.x, self.scale.y * child.scale.y),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn close(a: Vec2, b: Vec2) -> bool {
+        a.distance(b) < 1e-4
+    }
+
+    const POINTS: [(f32, f32); 4] = [(0.0, 0.0), (1.0, 0.0), (-3.0, 2.5), (7.0, -4.0)];
+
+    #[test]
+    fn inverse_apply_undoes_apply() {
+        let t = Transform2D::new(Vec2::new(3.0, -2.0), 0.7, Vec2::new(2.0, 0.5));
+        for &p in POINTS.iter() {
+            let p = Vec2::from(p);
+            assert!(close(t.inverse_apply(t.apply(p)), p), "{:?}", p);
+            assert!(close(t.apply(t.inverse_apply(p)), p), "{:?}", p);
+        }
+    }
+
+    #[test]
+    fn quarter_turns_go_clockwise_on_screen() {
+        let t = Transform2D::new(Vec2::new(10.0, 0.0), FRAC_PI_2, Vec2::new(2.0, 2.0));
+        assert!(close(t.apply(Vec2::new(1.0, 0.0)), Vec2::new(10.0, 2.0)));
+        assert!(close(t.apply(Vec2::new(0.0, 1.0)), Vec2::new(8.0, 0.0)));
+    }
+
+    #[test]
+    fn compose_matches_applying_in_turn() {
+        let child = Transform2D::new(Vec2::new(4.0, 1.0), -0.3, Vec2::new(0.5, 3.0));
+        // a rotated child under an evenly scaled parent, and an unrotated
+        // child under an unevenly scaled one
+        let cases = [
+            (
+                Transform2D::new(Vec2::new(-2.0, 5.0), 1.2, Vec2::new(2.0, 2.0)),
+                child,
+            ),
+            (
+                Transform2D::new(Vec2::new(-2.0, 5.0), 1.2, Vec2::new(2.0, 0.25)),
+                Transform2D {
+                    rotation: 0.0,
+                    ..child
+                },
+            ),
+        ];
+        for (parent, child) in cases.iter() {
+            let world = parent.compose(child);
+            for &p in POINTS.iter() {
+                let p = Vec2::from(p);
+                assert!(close(world.apply(p), parent.apply(child.apply(p))));
+                let back = child.inverse_apply(parent.inverse_apply(world.apply(p)));
+                assert!(close(back, p));
+            }
+        }
+    }
+}